@@ -0,0 +1,309 @@
+use std::error::Error;
+use std::path::Path;
+use std::process::ExitCode;
+
+use ihex_ext::srec::{self, SrecWriteOptions};
+use ihex_ext::ti_txt::{self, TiTxtWriteOptions};
+use ihex_ext::*;
+
+const USAGE: &str = "\
+Usage: ihex <command> [options]
+
+Commands:
+    info <file>                     Show segments, sizes, entry point and checksums
+    convert <input> <output>        Convert between .hex, .srec/.s19/.s28/.s37, .txt and .bin
+    dump <file>                     Print a hexdump of the data by address
+    diff <a> <b>                    Show the address ranges where two files differ
+    merge <output> <inputs>...      Combine files, failing if they conflict
+
+Options:
+    --base <address>                Address of raw binary inputs and outputs (default 0)
+    --start <address>               First address to dump
+    --len <bytes>                   Number of bytes to dump
+";
+
+type CliResult = Result<ExitCode, Box<dyn Error>>;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(code) => code,
+        Err(err) => {
+            // The library's errors already include their source in their message.
+            eprintln!("error: {}", err);
+            ExitCode::from(2)
+        }
+    }
+}
+
+struct Args {
+    positional: Vec<String>,
+    base: Option<usize>,
+    start: Option<usize>,
+    len: Option<usize>,
+}
+
+impl Args {
+    fn parse(args: &[String]) -> Result<Self, Box<dyn Error>> {
+        let mut parsed = Args {
+            positional: Vec::new(),
+            base: None,
+            start: None,
+            len: None,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {}", arg))
+                    .and_then(|value| parse_number(value))
+            };
+            match arg.as_str() {
+                "--base" => parsed.base = Some(value()?),
+                "--start" => parsed.start = Some(value()?),
+                "--len" => parsed.len = Some(value()?),
+                _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg).into()),
+                _ => parsed.positional.push(arg.clone()),
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn files(&self, count: usize) -> Result<&[String], Box<dyn Error>> {
+        if self.positional.len() != count {
+            return Err(format!("expected {} file arguments\n\n{}", count, USAGE).into());
+        }
+        Ok(&self.positional)
+    }
+}
+
+fn parse_number(value: &str) -> Result<usize, String> {
+    let parsed = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|_| format!("invalid number `{}`", value))
+}
+
+fn run(args: &[String]) -> CliResult {
+    let Some((command, rest)) = args.split_first() else {
+        print!("{}", USAGE);
+        return Ok(ExitCode::from(2));
+    };
+    let args = Args::parse(rest)?;
+    match command.as_str() {
+        "info" => info(&args),
+        "convert" => convert(&args),
+        "dump" => dump(&args),
+        "diff" => diff(&args),
+        "merge" => merge(&args),
+        "help" | "--help" | "-h" => {
+            print!("{}", USAGE);
+            Ok(ExitCode::SUCCESS)
+        }
+        _ => Err(format!("unknown command `{}`\n\n{}", command, USAGE).into()),
+    }
+}
+
+fn load(path: &str, args: &Args) -> Result<(FileFormat, Unpacked<MemoryImage>), Box<dyn Error>> {
+    let (format, unpacked) = load_file_auto(path, &UnpackOptions::new())?;
+    let base = args.base.unwrap_or(0);
+    if format != FileFormat::Binary || base == 0 {
+        return Ok((format, unpacked));
+    }
+    // Raw binaries are loaded at the start of the window, so move them to the requested
+    // address.
+    let moved = isize::try_from(base)
+        .ok()
+        .and_then(|offset| unpacked.data.relocated(offset))
+        .ok_or_else(|| format!("{} doesn't fit in the address space at 0x{:X}", path, base))?;
+    Ok((format, unpacked.map(|_| moved)))
+}
+
+fn info(args: &Args) -> CliResult {
+    let [path] = args.files(1)? else {
+        unreachable!()
+    };
+    let (format, unpacked) = load(path, args)?;
+    let image = &unpacked.data;
+
+    println!("format:      {:?}", format);
+    match (image.start_address(), image.end_address()) {
+        (Some(start), Some(end)) => println!("range:       0x{:08X}..0x{:08X}", start, end),
+        _ => println!("range:       empty"),
+    }
+    println!("data bytes:  {}", unpacked.used_bytes);
+    match unpacked.entry_point {
+        Some(EntryPoint::Linear(address)) => println!("entry point: 0x{:08X}", address),
+        Some(EntryPoint::Segment { cs, ip }) => println!("entry point: {:04X}:{:04X}", cs, ip),
+        None => println!("entry point: none"),
+    }
+
+    println!("segments:    {}", image.segments().len());
+    for segment in image.segments() {
+        println!(
+            "  0x{:08X}..0x{:08X}  {:>8} bytes  crc32 {:08X}",
+            segment.address(),
+            segment.end(),
+            segment.len(),
+            CrcParams::CRC32.compute(segment.data())
+        );
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn convert(args: &Args) -> CliResult {
+    let [input, output] = args.files(2)? else {
+        unreachable!()
+    };
+    let (_, unpacked) = load(input, args)?;
+    save(output, &unpacked.data, unpacked.entry_point, args)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn save(
+    path: &str,
+    image: &MemoryImage,
+    entry_point: Option<EntryPoint>,
+    args: &Args,
+) -> Result<(), Box<dyn Error>> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "hex" | "ihex" | "ihx" => {
+            let options = WriteOptions {
+                entry_point,
+                ..WriteOptions::default()
+            };
+            save_file_image(path, image, 0, &options)?;
+        }
+        "srec" | "s19" | "s28" | "s37" | "mot" => {
+            let options = SrecWriteOptions {
+                entry_point: entry_point.map(|entry_point| match entry_point {
+                    EntryPoint::Linear(address) => address,
+                    EntryPoint::Segment { cs, ip } => ((cs as u32) << 4) + ip as u32,
+                }),
+                ..SrecWriteOptions::default()
+            };
+            srec::save_srec_file_image(path, image, 0, &options)?;
+        }
+        "txt" => {
+            ti_txt::save_ti_txt_file_image(path, image, 0, &TiTxtWriteOptions::default())?;
+        }
+        "bin" => {
+            // Start from the first data byte unless asked to pad down to a lower address.
+            let start = image.start_address().unwrap_or(0);
+            let start = args.base.map_or(start, |base| base.min(start));
+            let end = image.end_address().unwrap_or(start);
+            save_bin_file(path, image, start..end, &BinExportOptions::default())?;
+        }
+        _ => return Err(format!("unknown output format for `{}`", path).into()),
+    }
+    Ok(())
+}
+
+fn dump(args: &Args) -> CliResult {
+    let [path] = args.files(1)? else {
+        unreachable!()
+    };
+    let image = load(path, args)?.1.data;
+    let start = args.start.or(image.start_address()).unwrap_or(0);
+    let end = match args.len {
+        Some(len) => start.saturating_add(len),
+        None => image.end_address().unwrap_or(0),
+    };
+
+    let mut row = start & !0xF;
+    while row < end {
+        // Skip rows with no data, so large gaps don't flood the output.
+        let cols = row.max(start)..(row + 16).min(end);
+        if !image.overlaps(cols.start, cols.len()) {
+            row = match image.segments().find(|s| s.end() > row + 16) {
+                Some(segment) => (segment.address() & !0xF).max(row + 16),
+                None => break,
+            };
+            continue;
+        }
+
+        let mut hex = String::new();
+        let mut ascii = String::new();
+        for address in row..row + 16 {
+            match image.get(address).filter(|_| cols.contains(&address)) {
+                Some(b) => {
+                    hex.push_str(&format!(" {:02X}", b));
+                    ascii.push(if b.is_ascii_graphic() { b as char } else { '.' });
+                }
+                None => {
+                    hex.push_str(" --");
+                    ascii.push(' ');
+                }
+            }
+        }
+        println!("{:08X} {}  |{}|", row, hex, ascii);
+        row += 16;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn diff(args: &Args) -> CliResult {
+    let [a, b] = args.files(2)? else {
+        unreachable!()
+    };
+    let a = load(a, args)?.1.data;
+    let b = load(b, args)?.1.data;
+
+    let diff = ihex_ext::diff(&a, &b);
+
+    for change in &diff.changes {
+        // Only show the first bytes of long ranges.
+        let side = |image: &MemoryImage| {
+            let mut bytes = change
+                .range
+                .clone()
+                .take(16)
+                .map(|address| {
+                    image
+                        .get(address)
+                        .map_or("--".into(), |b| format!("{:02X}", b))
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            if change.range.len() > 16 {
+                bytes.push_str(" ...");
+            }
+            bytes
+        };
+        println!("{}", change);
+        println!("  - {}", side(&a));
+        println!("  + {}", side(&b));
+    }
+    Ok(if diff.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+fn merge(args: &Args) -> CliResult {
+    let Some((output, inputs)) = args.positional.split_first() else {
+        return Err(format!("expected output and input files\n\n{}", USAGE).into());
+    };
+    if inputs.is_empty() {
+        return Err(format!("expected at least one input file\n\n{}", USAGE).into());
+    }
+
+    let inputs = inputs
+        .iter()
+        .map(|input| Ok(MergeInput::unpacked(load(input, args)?.1)))
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+    let merged = ihex_ext::merge(inputs, &UnpackOptions::new())?;
+
+    save(output, &merged.data, merged.entry_point, args)?;
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,118 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::{LoadError, MemoryImage, SaveError, WritingError};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum OutOfRange {
+    /// Fail if any data lies outside of the window.
+    #[default]
+    Error,
+    /// Export the part of any data inside the window, cutting it at the window's edges.
+    Clip,
+    /// Leave out any bytes outside of the window. This is the same as `Clip`, as data is exported
+    /// byte by byte rather than record by record.
+    Ignore,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BinExportOptions {
+    /// The byte written for gaps between data.
+    pub fill: u8,
+    pub out_of_range: OutOfRange,
+}
+
+impl Default for BinExportOptions {
+    fn default() -> Self {
+        BinExportOptions {
+            fill: 0xFF,
+            out_of_range: OutOfRange::default(),
+        }
+    }
+}
+
+/// Writes the bytes at the addresses in `window` to `writer` as a raw binary, in the same way
+/// as `objcopy -O binary`.
+pub fn write_bin<W: Write>(
+    image: &MemoryImage,
+    window: Range<usize>,
+    options: &BinExportOptions,
+    mut writer: W,
+) -> Result<(), SaveError> {
+    check_window(&window)?;
+    let mut exported = MemoryImage::new();
+    for segment in image.segments() {
+        let inside = segment.address() >= window.start && segment.end() <= window.end;
+        if inside {
+            exported.write(segment.address(), segment.data());
+            continue;
+        }
+        match options.out_of_range {
+            OutOfRange::Error => {
+                let address = if segment.address() < window.start {
+                    segment.address()
+                } else {
+                    segment.address().max(window.end)
+                };
+                return Err(WritingError::OutsideWindow(address).into());
+            }
+            OutOfRange::Clip | OutOfRange::Ignore => {
+                let start = segment.address().clamp(window.start, window.end);
+                let end = segment.end().clamp(window.start, window.end);
+                let data = &segment.data()[start - segment.address()..end - segment.address()];
+                exported.write(start, data);
+            }
+        }
+    }
+
+    let mut buf = [0; 4096];
+    let mut address = window.start;
+    while address < window.end {
+        let len = buf.len().min(window.end - address);
+        exported.copy_to_slice(address, &mut buf[..len], &[options.fill]);
+        writer
+            .write_all(&buf[..len])
+            .map_err(SaveError::FailedWrite)?;
+        address += len;
+    }
+    writer.flush().map_err(SaveError::FailedWrite)
+}
+
+pub fn save_bin_file<P: AsRef<Path>>(
+    path: P,
+    image: &MemoryImage,
+    window: Range<usize>,
+    options: &BinExportOptions,
+) -> Result<(), SaveError> {
+    check_window(&window)?;
+    let file = File::create(path).map_err(SaveError::FailedCreate)?;
+    write_bin(image, window, options, BufWriter::new(file))
+}
+
+fn check_window(window: &Range<usize>) -> Result<(), WritingError> {
+    if window.start > window.end {
+        return Err(WritingError::InvalidWindow(window.start, window.end));
+    }
+    Ok(())
+}
+
+/// Loads a raw binary file into an image, placing its first byte at `load_address`.
+pub fn load_bin_file<P: AsRef<Path>>(
+    path: P,
+    load_address: usize,
+) -> Result<MemoryImage, LoadError> {
+    let path = path.as_ref();
+    crate::in_file(path, || {
+        let bytes = crate::read_file_bytes(path)?;
+        if load_address.checked_add(bytes.len()).is_none() {
+            return Err(crate::UnpackingError::AddressOverflow {
+                address: load_address,
+                len: bytes.len(),
+            }
+            .into());
+        }
+        Ok(MemoryImage::from_slice(load_address, &bytes))
+    })
+}
@@ -0,0 +1,255 @@
+use std::ops::Range;
+
+use sha2::{Digest as _, Sha256};
+
+use thiserror::Error;
+
+use crate::MemoryImage;
+
+/// Parameters of a CRC in the usual Rocksoft model, for widths from 1 to 32 bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CrcParams {
+    width: u8,
+    poly: u32,
+    init: u32,
+    reflect_in: bool,
+    reflect_out: bool,
+    xor_out: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Error)]
+#[non_exhaustive]
+pub enum CrcError {
+    #[error("CRC width ({0}) must be between 1 and 32 bits")]
+    UnsupportedWidth(u8),
+}
+
+impl CrcParams {
+    /// CRC-32 as used by zip, Ethernet and most flashing tools.
+    pub const CRC32: CrcParams = CrcParams {
+        width: 32,
+        poly: 0x04C1_1DB7,
+        init: 0xFFFF_FFFF,
+        reflect_in: true,
+        reflect_out: true,
+        xor_out: 0xFFFF_FFFF,
+    };
+    /// CRC-32/MPEG-2, as computed by the STM32 CRC peripheral with its default settings.
+    pub const CRC32_MPEG2: CrcParams = CrcParams {
+        width: 32,
+        poly: 0x04C1_1DB7,
+        init: 0xFFFF_FFFF,
+        reflect_in: false,
+        reflect_out: false,
+        xor_out: 0,
+    };
+    /// CRC-16/CCITT-FALSE, also known as CRC-16/IBM-3740.
+    pub const CRC16_CCITT: CrcParams = CrcParams {
+        width: 16,
+        poly: 0x1021,
+        init: 0xFFFF,
+        reflect_in: false,
+        reflect_out: false,
+        xor_out: 0,
+    };
+    /// CRC-16/XMODEM.
+    pub const CRC16_XMODEM: CrcParams = CrcParams {
+        init: 0,
+        ..CrcParams::CRC16_CCITT
+    };
+    /// CRC-16/KERMIT, the reflected form of the CCITT CRC.
+    pub const CRC16_KERMIT: CrcParams = CrcParams {
+        init: 0,
+        reflect_in: true,
+        reflect_out: true,
+        ..CrcParams::CRC16_CCITT
+    };
+
+    /// A CRC with the polynomial `poly` in normal (non-reflected) form, without the top bit.
+    /// The initial value and final XOR default to zero, with no reflection.
+    pub fn new(width: u8, poly: u32) -> Result<Self, CrcError> {
+        if !(1..=32).contains(&width) {
+            return Err(CrcError::UnsupportedWidth(width));
+        }
+        let params = CrcParams {
+            width,
+            poly,
+            init: 0,
+            reflect_in: false,
+            reflect_out: false,
+            xor_out: 0,
+        };
+        Ok(CrcParams {
+            poly: poly & params.mask(),
+            ..params
+        })
+    }
+
+    pub fn init(mut self, init: u32) -> Self {
+        self.init = init & self.mask();
+        self
+    }
+
+    /// Whether each input byte is processed least significant bit first, and whether the final
+    /// register is bit reversed before the final XOR.
+    pub fn reflect(mut self, reflect_in: bool, reflect_out: bool) -> Self {
+        self.reflect_in = reflect_in;
+        self.reflect_out = reflect_out;
+        self
+    }
+
+    pub fn xor_out(mut self, xor_out: u32) -> Self {
+        self.xor_out = xor_out & self.mask();
+        self
+    }
+
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    pub fn compute(&self, data: &[u8]) -> u32 {
+        let mut crc = Crc::new(*self);
+        crc.update(data);
+        crc.finish()
+    }
+
+    fn mask(&self) -> u32 {
+        u32::MAX >> (32 - self.width as u32)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Checksum {
+    Crc(CrcParams),
+    Sha256,
+}
+
+impl Checksum {
+    pub fn compute(&self, data: &[u8]) -> Digest {
+        let mut state = State::new(*self);
+        state.update(data);
+        state.finish()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Digest {
+    Crc(u32),
+    Sha256([u8; 32]),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GapPolicy {
+    /// Checksum gaps as if they held this byte, as they would once programmed.
+    Fill(u8),
+    /// Only checksum the bytes held by the image.
+    Skip,
+}
+
+impl MemoryImage {
+    /// Computes `checksum` over the bytes in `range`, treating any bytes missing from the image
+    /// according to `gaps`.
+    pub fn checksum(&self, range: Range<usize>, checksum: Checksum, gaps: GapPolicy) -> Digest {
+        let mut state = State::new(checksum);
+        if range.start >= range.end {
+            return state.finish();
+        }
+        let mut address = range.start;
+        let fill = |state: &mut State, len: usize| {
+            if let GapPolicy::Fill(b) = gaps {
+                let chunk = [b; 256];
+                for n in (0..len).step_by(chunk.len()) {
+                    state.update(&chunk[..(len - n).min(chunk.len())]);
+                }
+            }
+        };
+
+        for segment in self.segments() {
+            if segment.end() <= address {
+                continue;
+            }
+            if segment.address() >= range.end {
+                break;
+            }
+            let start = segment.address().max(address);
+            let end = segment.end().min(range.end);
+            fill(&mut state, start - address);
+            state.update(&segment.data()[start - segment.address()..end - segment.address()]);
+            address = end;
+        }
+        fill(&mut state, range.end.saturating_sub(address));
+
+        state.finish()
+    }
+}
+
+enum State {
+    Crc(Crc),
+    Sha256(Sha256),
+}
+
+impl State {
+    fn new(checksum: Checksum) -> Self {
+        match checksum {
+            Checksum::Crc(params) => State::Crc(Crc::new(params)),
+            Checksum::Sha256 => State::Sha256(Sha256::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            State::Crc(crc) => crc.update(data),
+            State::Sha256(sha) => sha.update(data),
+        }
+    }
+
+    fn finish(self) -> Digest {
+        match self {
+            State::Crc(crc) => Digest::Crc(crc.finish()),
+            State::Sha256(sha) => Digest::Sha256(sha.finalize().into()),
+        }
+    }
+}
+
+struct Crc {
+    params: CrcParams,
+    // Always held in non-reflected form.
+    register: u32,
+}
+
+impl Crc {
+    fn new(params: CrcParams) -> Self {
+        Crc {
+            params,
+            register: params.init,
+        }
+    }
+
+    // Processes one bit at a time, so that widths below 8 bits work too.
+    fn update(&mut self, data: &[u8]) {
+        let top = self.params.width as u32 - 1;
+        for &b in data {
+            let b = if self.params.reflect_in {
+                b.reverse_bits()
+            } else {
+                b
+            };
+            for n in (0..8).rev() {
+                let feedback = ((b >> n) as u32 ^ (self.register >> top)) & 1;
+                self.register = (self.register << 1) & self.params.mask();
+                if feedback != 0 {
+                    self.register ^= self.params.poly;
+                }
+            }
+        }
+    }
+
+    fn finish(self) -> u32 {
+        let crc = if self.params.reflect_out {
+            self.register.reverse_bits() >> (32 - self.params.width as u32)
+        } else {
+            self.register
+        };
+        (crc ^ self.params.xor_out) & self.params.mask()
+    }
+}
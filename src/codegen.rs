@@ -0,0 +1,200 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::{MemoryImage, SaveError, WritingError};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodegenOptions {
+    /// The name of the generated array, used lowercase in C and uppercase in Rust. Must be a
+    /// valid identifier in both.
+    pub name: String,
+    /// Generates one array per segment, numbered from zero, rather than a single array covering
+    /// the whole image.
+    pub per_segment: bool,
+    /// The byte used for gaps between segments in a single array.
+    pub fill: u8,
+    pub bytes_per_line: usize,
+}
+
+impl Default for CodegenOptions {
+    fn default() -> Self {
+        CodegenOptions {
+            name: "fw".to_owned(),
+            per_segment: false,
+            fill: 0xFF,
+            bytes_per_line: 12,
+        }
+    }
+}
+
+// The arrays to generate, as name suffix, address and contents.
+fn arrays(
+    image: &MemoryImage,
+    base_address: usize,
+    options: &CodegenOptions,
+) -> Result<Vec<(String, usize, Vec<u8>)>, WritingError> {
+    let is_identifier = options
+        .name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && options
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && options.name != "_";
+    if !is_identifier {
+        return Err(WritingError::InvalidName(options.name.clone()));
+    }
+    // Neither language allows zero length arrays.
+    let (Some(start), Some(end)) = (image.start_address(), image.end_address()) else {
+        return Err(WritingError::EmptyImage);
+    };
+    // Checking the end of the image covers the address of every segment.
+    if base_address.checked_add(end).is_none() {
+        return Err(WritingError::BaseAddressOverflow(base_address));
+    }
+
+    if options.per_segment {
+        return Ok(image
+            .segments()
+            .enumerate()
+            .map(|(n, s)| {
+                (
+                    format!("_{}", n),
+                    base_address + s.address(),
+                    s.data().to_vec(),
+                )
+            })
+            .collect());
+    }
+
+    let mut data = vec![0; end - start];
+    image.copy_to_slice(start, &mut data, &[options.fill]);
+    Ok(vec![(String::new(), base_address + start, data)])
+}
+
+fn write_bytes(out: &mut String, data: &[u8], options: &CodegenOptions) {
+    for line in data.chunks(options.bytes_per_line.max(1)) {
+        out.push_str("   ");
+        for b in line {
+            write!(out, " 0x{:02X},", b).unwrap();
+        }
+        out.push('\n');
+    }
+}
+
+/// Generates a C header declaring the image as `static const uint8_t` arrays, along with
+/// `_ADDRESS` and `_SIZE` macros for each.
+pub fn image_to_c(
+    image: &MemoryImage,
+    base_address: usize,
+    options: &CodegenOptions,
+) -> Result<String, WritingError> {
+    let arrays = arrays(image, base_address, options)?;
+    let name = options.name.to_ascii_lowercase();
+    let upper = options.name.to_ascii_uppercase();
+
+    let mut out = String::new();
+    writeln!(out, "/* Generated by ihex_ext. Do not edit. */").unwrap();
+    writeln!(out, "#ifndef {}_H\n#define {}_H\n", upper, upper).unwrap();
+    writeln!(out, "#include <stdint.h>\n").unwrap();
+    if options.per_segment {
+        writeln!(
+            out,
+            "#define {}_SEGMENT_COUNT {}u\n",
+            upper,
+            image.segments().len()
+        )
+        .unwrap();
+    }
+    for (suffix, address, data) in arrays {
+        let upper_suffix = suffix.to_ascii_uppercase();
+        writeln!(
+            out,
+            "#define {}{}_ADDRESS 0x{:08X}u",
+            upper, upper_suffix, address
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "#define {}{}_SIZE {}u",
+            upper,
+            upper_suffix,
+            data.len()
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "static const uint8_t {}{}[{}] = {{",
+            name,
+            suffix,
+            data.len()
+        )
+        .unwrap();
+        write_bytes(&mut out, &data, options);
+        writeln!(out, "}};\n").unwrap();
+    }
+    writeln!(out, "#endif").unwrap();
+    Ok(out)
+}
+
+/// Generates Rust source declaring the image as `pub static` arrays, along with an `_ADDRESS`
+/// constant for each and a `_SEGMENT_COUNT` constant when generating one array per segment. Suitable for `include!` from a build script's output.
+pub fn image_to_rust(
+    image: &MemoryImage,
+    base_address: usize,
+    options: &CodegenOptions,
+) -> Result<String, WritingError> {
+    let arrays = arrays(image, base_address, options)?;
+    let upper = options.name.to_ascii_uppercase();
+
+    let mut out = String::new();
+    writeln!(out, "// Generated by ihex_ext. Do not edit.\n").unwrap();
+    if options.per_segment {
+        writeln!(
+            out,
+            "pub const {}_SEGMENT_COUNT: usize = {};\n",
+            upper,
+            image.segments().len()
+        )
+        .unwrap();
+    }
+    for (suffix, address, data) in arrays {
+        writeln!(
+            out,
+            "pub const {}{}_ADDRESS: usize = 0x{:08X};",
+            upper, suffix, address
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "pub static {}{}: [u8; {}] = [",
+            upper,
+            suffix,
+            data.len()
+        )
+        .unwrap();
+        write_bytes(&mut out, &data, options);
+        writeln!(out, "];\n").unwrap();
+    }
+    Ok(out)
+}
+
+pub fn save_c_header<P: AsRef<Path>>(
+    path: P,
+    image: &MemoryImage,
+    base_address: usize,
+    options: &CodegenOptions,
+) -> Result<(), SaveError> {
+    crate::write_file(path, image_to_c(image, base_address, options)?)
+}
+
+pub fn save_rust_source<P: AsRef<Path>>(
+    path: P,
+    image: &MemoryImage,
+    base_address: usize,
+    options: &CodegenOptions,
+) -> Result<(), SaveError> {
+    crate::write_file(path, image_to_rust(image, base_address, options)?)
+}
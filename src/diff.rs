@@ -0,0 +1,104 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::MemoryImage;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    /// Only the new image holds data in the range.
+    Added,
+    /// Only the old image holds data in the range.
+    Removed,
+    /// Both images hold data in the range, but it differs.
+    Changed,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Changed => "changed",
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub range: Range<usize>,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:<7} 0x{:08X}..0x{:08X} ({} bytes)",
+            self.kind,
+            self.range.start,
+            self.range.end,
+            self.range.len()
+        )
+    }
+}
+
+/// The address ranges that differ between two images, sorted by address. Displays as a report
+/// with one line per change.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImageDiff {
+    pub changes: Vec<Change>,
+}
+
+impl ImageDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    fn push(&mut self, kind: ChangeKind, range: Range<usize>) {
+        match self.changes.last_mut() {
+            Some(last) if last.kind == kind && last.range.end == range.start => {
+                last.range.end = range.end
+            }
+            _ => self.changes.push(Change { kind, range }),
+        }
+    }
+}
+
+impl fmt::Display for ImageDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares two images by address, such as those unpacked from two versions of a file.
+pub fn diff(old: &MemoryImage, new: &MemoryImage) -> ImageDiff {
+    // Which image holds data only changes at segment boundaries, so walk the ranges between them.
+    let mut bounds: Vec<usize> = old
+        .segments()
+        .chain(new.segments())
+        .flat_map(|s| [s.address(), s.end()])
+        .collect();
+    bounds.sort_unstable();
+    bounds.dedup();
+
+    let mut diff = ImageDiff::default();
+    for range in bounds.windows(2) {
+        let (start, end) = (range[0], range[1]);
+        match (old.contains(start), new.contains(start)) {
+            (true, true) => {
+                for address in start..end {
+                    if old.get(address) != new.get(address) {
+                        diff.push(ChangeKind::Changed, address..address + 1);
+                    }
+                }
+            }
+            (true, false) => diff.push(ChangeKind::Removed, start..end),
+            (false, true) => diff.push(ChangeKind::Added, start..end),
+            (false, false) => {}
+        }
+    }
+    diff
+}
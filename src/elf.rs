@@ -0,0 +1,153 @@
+use std::path::Path;
+
+use log::*;
+use thiserror::Error;
+
+use crate::unpack::Unpacker;
+use crate::{EntryPoint, LoadError, MemoryImage, UnpackOptions, Unpacked, UnpackingError};
+
+const PT_LOAD: u32 = 1;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum LoadAddress {
+    /// Place segments at their physical (load) address, as `objcopy` does.
+    #[default]
+    Physical,
+    /// Place segments at their virtual (run) address.
+    Virtual,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Error)]
+#[non_exhaustive]
+pub enum ElfError {
+    #[error("missing ELF magic number")]
+    MissingMagic,
+    #[error("unsupported ELF class ({0})")]
+    UnsupportedClass(u8),
+    #[error("unsupported ELF data encoding ({0})")]
+    UnsupportedEncoding(u8),
+    #[error("file is truncated")]
+    Truncated,
+    #[error("address ({0:#X}) does not fit in the address space")]
+    AddressTooHigh(u64),
+}
+
+#[derive(Clone, Copy)]
+struct Layout {
+    is_64: bool,
+    big_endian: bool,
+}
+
+impl Layout {
+    fn read(&self, bytes: &[u8], offset: usize, len: usize) -> Result<u64, ElfError> {
+        let field = offset
+            .checked_add(len)
+            .and_then(|end| bytes.get(offset..end))
+            .ok_or(ElfError::Truncated)?;
+        let fold = |acc: u64, b: &u8| (acc << 8) | *b as u64;
+        Ok(if self.big_endian {
+            field.iter().fold(0, fold)
+        } else {
+            field.iter().rev().fold(0, fold)
+        })
+    }
+
+    // Reads a field that is 4 bytes in ELF32 and 8 bytes in ELF64.
+    fn read_word(&self, bytes: &[u8], offset: usize) -> Result<u64, ElfError> {
+        self.read(bytes, offset, if self.is_64 { 8 } else { 4 })
+    }
+}
+
+pub fn unpack_elf(
+    bytes: &[u8],
+    options: &UnpackOptions,
+    load_address: LoadAddress,
+) -> Result<Unpacked<MemoryImage>, UnpackingError> {
+    if !bytes.starts_with(b"\x7FELF") {
+        return Err(ElfError::MissingMagic.into());
+    }
+    let layout = Layout {
+        is_64: match bytes.get(4) {
+            Some(1) => false,
+            Some(2) => true,
+            Some(&class) => return Err(ElfError::UnsupportedClass(class).into()),
+            None => return Err(ElfError::Truncated.into()),
+        },
+        big_endian: match bytes.get(5) {
+            Some(1) => false,
+            Some(2) => true,
+            Some(&encoding) => return Err(ElfError::UnsupportedEncoding(encoding).into()),
+            None => return Err(ElfError::Truncated.into()),
+        },
+    };
+
+    let (phoff, phentsize, phnum) = if layout.is_64 {
+        (32, 54, 56)
+    } else {
+        (28, 42, 44)
+    };
+    let entry = layout.read_word(bytes, 24)?;
+    let phoff = to_usize(layout.read_word(bytes, phoff)?)?;
+    let phentsize = layout.read(bytes, phentsize, 2)? as usize;
+    let phnum = layout.read(bytes, phnum, 2)? as usize;
+
+    let mut unpacker = Unpacker::new(options, options.size_limit);
+    for n in 0..phnum {
+        let header = phoff
+            .checked_add(n * phentsize)
+            .and_then(|start| bytes.get(start..start.checked_add(phentsize)?))
+            .ok_or(ElfError::Truncated)?;
+        let (offset, vaddr, paddr, filesz) = if layout.is_64 {
+            (8, 16, 24, 32)
+        } else {
+            (4, 8, 12, 16)
+        };
+
+        let p_type = layout.read(header, 0, 4)? as u32;
+        let filesz = to_usize(layout.read_word(header, filesz)?)?;
+        if p_type != PT_LOAD || filesz == 0 {
+            continue;
+        }
+
+        let offset = to_usize(layout.read_word(header, offset)?)?;
+        let address = match load_address {
+            LoadAddress::Physical => layout.read_word(header, paddr)?,
+            LoadAddress::Virtual => layout.read_word(header, vaddr)?,
+        };
+        debug!(
+            "PT_LOAD address=0x{:08X} offset=0x{:X} filesz=0x{:X}",
+            address, offset, filesz
+        );
+
+        let data = offset
+            .checked_add(filesz)
+            .and_then(|end| bytes.get(offset..end))
+            .ok_or(ElfError::Truncated)?;
+        unpacker.data(to_usize(address)?, data)?;
+    }
+
+    // An entry point of zero usually means there is none. One too large for a 32-bit start
+    // address, such as in a high half kernel, shouldn't stop its segments from being loaded.
+    match u32::try_from(entry) {
+        Ok(0) if !options.zero_entry_point => {}
+        Ok(entry) => unpacker.entry_point(EntryPoint::Linear(entry))?,
+        Err(_) => warn!("ignoring entry point 0x{:X} beyond 32 bits", entry),
+    }
+    unpacker.finish(true)
+}
+
+fn to_usize(value: u64) -> Result<usize, ElfError> {
+    usize::try_from(value).map_err(|_| ElfError::AddressTooHigh(value))
+}
+
+pub fn load_elf_file_image<P: AsRef<Path>>(
+    path: P,
+    options: &UnpackOptions,
+    load_address: LoadAddress,
+) -> Result<Unpacked<MemoryImage>, LoadError> {
+    let path = path.as_ref();
+    crate::in_file(path, || {
+        let bytes = crate::read_file_bytes(path)?;
+        Ok(unpack_elf(&bytes, options, load_address)?)
+    })
+}
@@ -0,0 +1,99 @@
+use std::path::Path;
+
+use crate::elf::{self, LoadAddress};
+use crate::srec;
+use crate::ti_txt;
+use crate::uf2;
+use crate::unpack::Unpacker;
+use crate::{LoadError, MemoryImage, UnpackOptions, Unpacked};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FileFormat {
+    IntelHex,
+    Srec,
+    TiTxt,
+    Elf,
+    Uf2,
+    /// Raw binary with no address information.
+    Binary,
+    /// Text that doesn't look like any of the supported formats.
+    Unknown,
+}
+
+/// Guesses the format of a file from its first bytes.
+pub fn detect_format(bytes: &[u8]) -> FileFormat {
+    if bytes.starts_with(b"\x7FELF") {
+        return FileFormat::Elf;
+    }
+    if bytes.starts_with(b"UF2\n\x57\x51\x5D\x9E") {
+        return FileFormat::Uf2;
+    }
+
+    // All of the text formats start with a distinctive character on their first line, which
+    // should be printable ASCII.
+    let text = bytes
+        .iter()
+        .skip_while(|b| b.is_ascii_whitespace())
+        .take_while(|b| **b != b'\n' && **b != b'\r');
+    let mut line = Vec::new();
+    for &b in text.take(80) {
+        if !(b.is_ascii_graphic() || b == b' ' || b == b'\t') {
+            return FileFormat::Binary;
+        }
+        line.push(b);
+    }
+
+    // Only the start code is checked, so that a corrupt first record is reported as such when
+    // the file is loaded rather than treating the file as something else.
+    match line.as_slice() {
+        [b':', ..] => FileFormat::IntelHex,
+        [b'S', b'0'..=b'9', ..] => FileFormat::Srec,
+        [b'@', ..] => FileFormat::TiTxt,
+        _ if crate::check_text(bytes).is_ok() => FileFormat::Unknown,
+        _ => FileFormat::Binary,
+    }
+}
+
+/// Loads a file in any supported format, detecting the format from its contents.
+pub fn load_file_auto<P: AsRef<Path>>(
+    path: P,
+    options: &UnpackOptions,
+) -> Result<(FileFormat, Unpacked<MemoryImage>), LoadError> {
+    let path = path.as_ref();
+    crate::in_file(path, || load_auto(path, options))
+}
+
+fn load_auto(
+    path: &Path,
+    options: &UnpackOptions,
+) -> Result<(FileFormat, Unpacked<MemoryImage>), LoadError> {
+    let bytes = crate::read_file_bytes(path)?;
+    let format = detect_format(&bytes);
+
+    let unpacked = match format {
+        FileFormat::IntelHex => {
+            let text = crate::check_text(&bytes)?;
+            crate::unpack_records(crate::ihex_records(text), options, options.size_limit)?
+        }
+        FileFormat::Srec => {
+            let text = crate::check_text(&bytes)?;
+            srec::unpack_srec_records(srec::srec_records(text), options, options.size_limit)?
+        }
+        FileFormat::Unknown => return Err(LoadError::UnknownFormat),
+        FileFormat::Binary => {
+            // Raw binaries carry no addresses, so place them at the start of the window.
+            let mut unpacker = Unpacker::new(options, options.size_limit);
+            unpacker.data(options.base_offset, &bytes)?;
+            unpacker.finish(true)?
+        }
+        FileFormat::Elf => elf::unpack_elf(&bytes, options, LoadAddress::default())?,
+        FileFormat::Uf2 => uf2::unpack_uf2(&bytes, options, None)?,
+        FileFormat::TiTxt => {
+            let text = crate::check_text(&bytes)?;
+            let records = ti_txt::ti_txt_records(text);
+            ti_txt::unpack_ti_txt_records(records, options, options.size_limit)?
+        }
+    };
+
+    Ok((format, unpacked))
+}
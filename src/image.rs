@@ -0,0 +1,237 @@
+use std::cmp;
+use std::ops::Range;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    address: usize,
+    data: Vec<u8>,
+}
+
+impl Segment {
+    pub fn address(&self) -> usize {
+        self.address
+    }
+
+    pub fn end(&self) -> usize {
+        self.address + self.data.len()
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.address..self.end()
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+/// A sparse memory image made up of non-overlapping, non-adjacent segments sorted by address.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryImage {
+    segments: Vec<Segment>,
+}
+
+impl MemoryImage {
+    pub fn new() -> Self {
+        MemoryImage::default()
+    }
+
+    pub fn from_slice(address: usize, data: &[u8]) -> Self {
+        let mut image = MemoryImage::new();
+        image.write(address, data);
+        image
+    }
+
+    pub fn segments(&self) -> impl ExactSizeIterator<Item = &Segment> + DoubleEndedIterator {
+        self.segments.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// The number of bytes held by the image, not counting gaps between segments.
+    pub fn len(&self) -> usize {
+        self.segments.iter().map(Segment::len).sum()
+    }
+
+    pub fn start_address(&self) -> Option<usize> {
+        self.segments.first().map(Segment::address)
+    }
+
+    pub fn end_address(&self) -> Option<usize> {
+        self.segments.last().map(Segment::end)
+    }
+
+    pub fn get(&self, address: usize) -> Option<u8> {
+        let idx = self.segments.partition_point(|s| s.end() <= address);
+        let segment = self.segments.get(idx)?;
+        if segment.address <= address {
+            Some(segment.data[address - segment.address])
+        } else {
+            None
+        }
+    }
+
+    pub fn contains(&self, address: usize) -> bool {
+        self.get(address).is_some()
+    }
+
+    /// Whether any byte in `address..address + len` is already held by the image.
+    pub fn overlaps(&self, address: usize, len: usize) -> bool {
+        let idx = self.segments.partition_point(|s| s.end() <= address);
+        self.segments
+            .get(idx)
+            .is_some_and(|s| s.address < address.saturating_add(len))
+    }
+
+    /// The lowest address at which both images hold a byte and the bytes differ.
+    pub fn first_conflict(&self, other: &MemoryImage) -> Option<usize> {
+        other.segments.iter().find_map(|theirs| {
+            let first = self.segments.partition_point(|s| s.end() <= theirs.address);
+            self.segments[first..]
+                .iter()
+                .take_while(|s| s.address < theirs.end())
+                .find_map(|ours| {
+                    let start = cmp::max(ours.address, theirs.address);
+                    let end = cmp::min(ours.end(), theirs.end());
+                    (start..end).find(|&address| {
+                        ours.data[address - ours.address] != theirs.data[address - theirs.address]
+                    })
+                })
+        })
+    }
+
+    /// Returns a copy of the image moved by `offset` bytes, or `None` if that would move any of
+    /// it outside of the address space.
+    pub fn relocated(&self, offset: isize) -> Option<MemoryImage> {
+        self.end_address()
+            .map_or(Some(0), |end| end.checked_add_signed(offset))?;
+        let segments = self
+            .segments
+            .iter()
+            .map(|s| {
+                Some(Segment {
+                    address: s.address.checked_add_signed(offset)?,
+                    data: s.data.clone(),
+                })
+            })
+            .collect::<Option<_>>()?;
+        Some(MemoryImage { segments })
+    }
+
+    /// Writes `data` at `address`, overwriting any bytes already present and merging the
+    /// result with any segments it overlaps or touches. Any data that would run past the end of
+    /// the address space is dropped.
+    pub fn write(&mut self, address: usize, data: &[u8]) {
+        let data = clip_to_address_space(address, data);
+        if data.is_empty() {
+            return;
+        }
+        let end = address + data.len();
+
+        // Segments in `first..last` overlap or are adjacent to the written range.
+        let first = self.segments.partition_point(|s| s.end() < address);
+        let last = self.segments.partition_point(|s| s.address <= end);
+
+        if first == last {
+            self.segments.insert(
+                first,
+                Segment {
+                    address,
+                    data: data.to_vec(),
+                },
+            );
+            return;
+        }
+
+        // The common case of appending to, or overwriting within, a single segment can be done
+        // in place.
+        if last - first == 1 && self.segments[first].address <= address {
+            let segment = &mut self.segments[first];
+            let start = address - segment.address;
+            if end > segment.end() {
+                segment.data.resize(end - segment.address, 0);
+            }
+            segment.data[start..start + data.len()].copy_from_slice(data);
+            return;
+        }
+
+        let merged_start = cmp::min(address, self.segments[first].address);
+        let merged_end = cmp::max(end, self.segments[last - 1].end());
+        let mut merged = vec![0; merged_end - merged_start];
+        for segment in self.segments.drain(first..last) {
+            let start = segment.address - merged_start;
+            merged[start..start + segment.data.len()].copy_from_slice(&segment.data);
+        }
+        merged[address - merged_start..end - merged_start].copy_from_slice(data);
+
+        self.segments.insert(
+            first,
+            Segment {
+                address: merged_start,
+                data: merged,
+            },
+        );
+    }
+
+    /// Writes only the bytes of `data` that would not overwrite bytes already held by the image.
+    pub(crate) fn write_unset(&mut self, address: usize, data: &[u8]) {
+        let data = clip_to_address_space(address, data);
+        let mut start = 0;
+        while start < data.len() {
+            if self.contains(address + start) {
+                start += 1;
+                continue;
+            }
+            let mut end = start + 1;
+            while end < data.len() && !self.contains(address + end) {
+                end += 1;
+            }
+            self.write(address + start, &data[start..end]);
+            start = end;
+        }
+    }
+
+    /// Copies the bytes at `address..address + binary.len()` into `binary`, filling any gaps
+    /// with the repeating `fill` pattern, aligned so that the byte at address `n` is
+    /// `fill[n % fill.len()]`.
+    pub fn copy_to_slice(&self, address: usize, binary: &mut [u8], fill: &[u8]) {
+        let end = address.saturating_add(binary.len());
+        for (n, b) in binary.iter_mut().enumerate() {
+            *b = fill[address.wrapping_add(n) % fill.len()];
+        }
+
+        let first = self.segments.partition_point(|s| s.end() <= address);
+        for segment in self.segments[first..]
+            .iter()
+            .take_while(|s| s.address < end)
+        {
+            let start = cmp::max(segment.address, address);
+            let stop = cmp::min(segment.end(), end);
+            binary[start - address..stop - address]
+                .copy_from_slice(&segment.data[start - segment.address..stop - segment.address]);
+        }
+    }
+
+    /// Flattens the image into a single buffer starting at address zero, filling any gaps with
+    /// 0xFF. Also returns the number of bytes actually held by the image.
+    pub fn to_vec(&self) -> (Vec<u8>, usize) {
+        let mut binary = vec![0; self.end_address().unwrap_or(0)];
+        self.copy_to_slice(0, &mut binary, &[0xFF]);
+        (binary, self.len())
+    }
+}
+
+// Segment ends must fit in a `usize`, so the last address can never hold data.
+fn clip_to_address_space(address: usize, data: &[u8]) -> &[u8] {
+    &data[..data.len().min(usize::MAX - address)]
+}
@@ -1,19 +1,110 @@
 use std::fs::File;
-use std::io::{self, Read};
-use std::path::Path;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str;
 
-use ihex::{Reader, ReaderError, Record};
+use ihex::{ReaderError, Record};
 use log::*;
 use thiserror::Error;
 
+mod binary;
+mod checksum;
+mod diff;
+mod format;
+mod image;
+mod merge;
+mod options;
+mod patch;
+mod records;
+mod unpack;
+mod writer;
+
+pub mod codegen;
+pub mod elf;
+pub mod mem_init;
+pub mod srec;
+pub mod ti_txt;
+pub mod uf2;
+
+pub use binary::{load_bin_file, save_bin_file, write_bin, BinExportOptions, OutOfRange};
+pub use checksum::{Checksum, CrcError, CrcParams, Digest, GapPolicy};
+pub use diff::{diff, Change, ChangeKind, ImageDiff};
+pub use format::{detect_format, load_file_auto, FileFormat};
+pub use image::{MemoryImage, Segment};
+pub use merge::{merge, MergeError, MergeInput};
+pub use options::{AddressUnit, OutOfWindow, OverlapPolicy, UnpackOptions};
+pub use patch::{Endian, PatchError, Patcher};
+pub use unpack::Unpacked;
+pub use writer::{image_to_records, image_to_string, AddressMode, WriteOptions, WritingError};
+
+use records::{numbered, ReaderRecords, RecordSource, Records};
+use unpack::Unpacker;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryPoint {
+    /// The 32-bit linear address loaded into EIP.
+    Linear(u32),
+    /// The CS:IP register pair of an 8086-style segmented address.
+    Segment { cs: u16, ip: u16 },
+}
+
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum LoadError {
-    #[error("IO error when opening file")]
-    FailedOpen(#[source] io::Error),
-    #[error("IO error when reading file")]
-    FailedRead(#[source] io::Error),
-    #[error("Error while unpacking IHEX into array")]
-    Unpacking(#[from] UnpackingError),
+    #[error("IO error when opening file: {0}")]
+    FailedOpen(io::Error),
+    #[error("IO error when reading file: {0}")]
+    FailedRead(io::Error),
+    #[error("Error while unpacking IHEX into array: {0}")]
+    Unpacking(UnpackingError),
+    #[error("File contains a non-text byte at offset {offset}")]
+    NotText { offset: usize },
+    #[error("File looks like a raw binary or ELF file rather than text")]
+    LooksLikeBinary,
+    #[error("File is text, but not in any supported format")]
+    UnknownFormat,
+    #[error("{}: {error}", path.display())]
+    InFile {
+        path: PathBuf,
+        error: Box<LoadError>,
+    },
+}
+
+// Error messages include their cause, so causes aren't also exposed as a `source` for reporters
+// to print a second time.
+impl From<UnpackingError> for LoadError {
+    fn from(err: UnpackingError) -> Self {
+        LoadError::Unpacking(err)
+    }
+}
+
+impl LoadError {
+    /// The path of the file that failed to load, if known.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            LoadError::InFile { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// The underlying error, without the file path.
+    pub fn kind(&self) -> &LoadError {
+        match self {
+            LoadError::InFile { error, .. } => error.kind(),
+            _ => self,
+        }
+    }
+}
+
+// Runs `load` and adds the path of the file to any error.
+pub(crate) fn in_file<T>(
+    path: &Path,
+    load: impl FnOnce() -> Result<T, LoadError>,
+) -> Result<T, LoadError> {
+    load().map_err(|error| LoadError::InFile {
+        path: path.to_path_buf(),
+        error: Box::new(error),
+    })
 }
 
 pub fn load_file_vec<P: AsRef<Path>>(
@@ -21,38 +112,290 @@ pub fn load_file_vec<P: AsRef<Path>>(
     binary_size: usize,
     base_offset: usize,
 ) -> Result<(Vec<u8>, usize), LoadError> {
-    let mut file = File::open(path).map_err(LoadError::FailedOpen)?;
-    let mut file_buf = Vec::new();
-    file.read_to_end(&mut file_buf)
-        .map_err(LoadError::FailedRead)?;
-
-    let file_str = String::from_utf8_lossy(&file_buf[..]);
-    Reader::new(&file_str)
-        .to_vec(binary_size, base_offset)
-        .map_err(LoadError::from)
+    let options = UnpackOptions::new()
+        .base_offset(base_offset)
+        .size_limit(binary_size);
+    let unpacked = load_file_vec_with(path, &options)?;
+    Ok((unpacked.data, unpacked.used_bytes))
 }
 
 pub fn load_file_array<P: AsRef<Path>, const N: usize>(
     path: P,
     base_offset: usize,
 ) -> Result<([u8; N], usize), LoadError> {
+    let options = UnpackOptions::new().base_offset(base_offset);
+    let unpacked = load_file_array_with::<P, N>(path, &options)?;
+    Ok((unpacked.data, unpacked.used_bytes))
+}
+
+pub fn load_file_vec_with<P: AsRef<Path>>(
+    path: P,
+    options: &UnpackOptions,
+) -> Result<Unpacked<Vec<u8>>, LoadError> {
+    Ok(load_file_image(path, options)?.into_vec(options))
+}
+
+pub fn load_file_array_with<P: AsRef<Path>, const N: usize>(
+    path: P,
+    options: &UnpackOptions,
+) -> Result<Unpacked<[u8; N]>, LoadError> {
+    let path = path.as_ref();
+    in_file(path, || {
+        let size_limit = unpack::array_size_limit::<N>(options);
+        Ok(unpack_reader(open_file(path)?, options, size_limit)?.into_array(options))
+    })
+}
+
+pub fn load_file_image<P: AsRef<Path>>(
+    path: P,
+    options: &UnpackOptions,
+) -> Result<Unpacked<MemoryImage>, LoadError> {
+    let path = path.as_ref();
+    in_file(path, || load_reader_image(open_file(path)?, options))
+}
+
+pub fn load_reader_vec<R: BufRead>(
+    reader: R,
+    binary_size: usize,
+    base_offset: usize,
+) -> Result<(Vec<u8>, usize), LoadError> {
+    let options = UnpackOptions::new()
+        .base_offset(base_offset)
+        .size_limit(binary_size);
+    let unpacked = load_reader_vec_with(reader, &options)?;
+    Ok((unpacked.data, unpacked.used_bytes))
+}
+
+pub fn load_reader_vec_with<R: BufRead>(
+    reader: R,
+    options: &UnpackOptions,
+) -> Result<Unpacked<Vec<u8>>, LoadError> {
+    Ok(load_reader_image(reader, options)?.into_vec(options))
+}
+
+pub fn load_reader_image<R: BufRead>(
+    reader: R,
+    options: &UnpackOptions,
+) -> Result<Unpacked<MemoryImage>, LoadError> {
+    unpack_reader(reader, options, options.size_limit)
+}
+
+fn unpack_reader<R: BufRead>(
+    reader: R,
+    options: &UnpackOptions,
+    size_limit: Option<usize>,
+) -> Result<Unpacked<MemoryImage>, LoadError> {
+    unpack_text(reader, Record::from_record_string, |records| {
+        unpack_records(records, options, size_limit)
+    })
+}
+
+// Unpacks the text records read from `reader`, rejecting anything that isn't text.
+pub(crate) fn unpack_text<R: BufRead, T>(
+    mut reader: R,
+    parse: fn(&str) -> T,
+    unpack: impl FnOnce(&mut ReaderRecords<R, T>) -> Result<Unpacked<MemoryImage>, UnpackingError>,
+) -> Result<Unpacked<MemoryImage>, LoadError> {
+    let start = reader.fill_buf().map_err(LoadError::FailedRead)?;
+    if looks_like_binary(start) {
+        return Err(LoadError::LooksLikeBinary);
+    }
+
+    let mut records = ReaderRecords::new(reader, parse);
+    let unpacked = unpack(&mut records);
+    // A read error or non-text data cuts the records short, which is the real cause of any
+    // unpacking error.
+    if let Some(err) = records.error {
+        return Err(err);
+    }
+    Ok(unpacked?)
+}
+
+// Text formats never contain NUL bytes, while raw binaries and ELF files almost always do.
+fn looks_like_binary(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"\x7FELF") || bytes.contains(&0)
+}
+
+// Checks that `bytes` is text before it is parsed as such.
+pub(crate) fn check_text(bytes: &[u8]) -> Result<&str, LoadError> {
+    if looks_like_binary(bytes) {
+        return Err(LoadError::LooksLikeBinary);
+    }
+    match bytes.iter().position(|b| !records::is_text(*b)) {
+        Some(offset) => Err(LoadError::NotText { offset }),
+        // Only ASCII remains, so this can't fail.
+        None => Ok(str::from_utf8(bytes).unwrap()),
+    }
+}
+
+pub(crate) fn open_file(path: &Path) -> Result<BufReader<File>, LoadError> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(LoadError::FailedOpen)
+}
+
+pub(crate) fn ihex_records(string: &str) -> Records<'_, Result<Record, ReaderError>> {
+    Records::new(string, Record::from_record_string)
+}
+
+pub(crate) fn read_file_bytes<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, LoadError> {
     let mut file = File::open(path).map_err(LoadError::FailedOpen)?;
     let mut file_buf = Vec::new();
     file.read_to_end(&mut file_buf)
         .map_err(LoadError::FailedRead)?;
 
-    let file_str = String::from_utf8_lossy(&file_buf[..]);
-    Reader::new(&file_str)
-        .to_array::<N>(base_offset)
-        .map_err(LoadError::from)
+    Ok(file_buf)
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum SaveError {
+    #[error("IO error when creating file: {0}")]
+    FailedCreate(io::Error),
+    #[error("IO error when writing file: {0}")]
+    FailedWrite(io::Error),
+    #[error("Error while packing image for output: {0}")]
+    Writing(WritingError),
+}
+
+impl From<WritingError> for SaveError {
+    fn from(err: WritingError) -> Self {
+        SaveError::Writing(err)
+    }
+}
+
+pub fn save_file<P: AsRef<Path>>(
+    path: P,
+    binary: &[u8],
+    base_address: usize,
+    options: &WriteOptions,
+) -> Result<(), SaveError> {
+    save_file_image(
+        path,
+        &MemoryImage::from_slice(0, binary),
+        base_address,
+        options,
+    )
+}
+
+pub fn save_file_image<P: AsRef<Path>>(
+    path: P,
+    image: &MemoryImage,
+    base_address: usize,
+    options: &WriteOptions,
+) -> Result<(), SaveError> {
+    let ihex = image_to_string(image, base_address, options)?;
+    write_file(path, &ihex)
+}
+
+pub(crate) fn write_file<P: AsRef<Path>>(
+    path: P,
+    contents: impl AsRef<[u8]>,
+) -> Result<(), SaveError> {
+    let mut file = File::create(path).map_err(SaveError::FailedCreate)?;
+    file.write_all(contents.as_ref())
+        .map_err(SaveError::FailedWrite)
 }
 
-#[derive(Debug, PartialEq, Error)]
+#[derive(Clone, Debug, PartialEq, Error)]
+#[non_exhaustive]
 pub enum UnpackingError {
-    #[error("Error while parsing IHEX records")]
-    Parsing(#[from] ReaderError),
+    #[error("Error while parsing IHEX records: {0}")]
+    Parsing(ReaderError),
+    #[error("Error while parsing S-records: {0}")]
+    SrecParsing(srec::SrecError),
+    #[error("Error while parsing TI-TXT records: {0}")]
+    TiTxtParsing(ti_txt::TiTxtError),
+    #[error("Error while parsing UF2 file: {0}")]
+    Uf2Parsing(uf2::Uf2Error),
+    #[error("Error while parsing ELF file: {0}")]
+    ElfParsing(elf::ElfError),
     #[error("Address ({0}) greater than binary size ({1})")]
     AddressTooHigh(usize, usize),
+    #[error(
+        "Data at address ({address:#X}) of length ({len}) runs past the end of the address space"
+    )]
+    AddressOverflow { address: usize, len: usize },
+    #[error("Data record at address ({address}) of length ({len}) overlaps earlier data")]
+    Overlap { address: usize, len: usize },
+    #[error("Start address record ({1:X?}) conflicts with earlier start address ({0:X?})")]
+    ConflictingEntryPoint(EntryPoint, EntryPoint),
+    #[error("Records ended without an End Of File record")]
+    MissingEof,
+    #[error("Data found after End Of File record on line {line}")]
+    DataAfterEof { line: usize },
+    #[error("Address ({address}) less than base offset ({base_offset})")]
+    AddressBelowBase { address: usize, base_offset: usize },
+    #[error("Data record length ({0}) is not a whole number of words")]
+    PartialWord(usize),
+    #[error("Line {line} `{record}`: {error}")]
+    AtLine {
+        line: usize,
+        record: String,
+        error: Box<UnpackingError>,
+    },
+}
+
+impl From<ReaderError> for UnpackingError {
+    fn from(err: ReaderError) -> Self {
+        UnpackingError::Parsing(err)
+    }
+}
+
+impl From<srec::SrecError> for UnpackingError {
+    fn from(err: srec::SrecError) -> Self {
+        UnpackingError::SrecParsing(err)
+    }
+}
+
+impl From<ti_txt::TiTxtError> for UnpackingError {
+    fn from(err: ti_txt::TiTxtError) -> Self {
+        UnpackingError::TiTxtParsing(err)
+    }
+}
+
+impl From<uf2::Uf2Error> for UnpackingError {
+    fn from(err: uf2::Uf2Error) -> Self {
+        UnpackingError::Uf2Parsing(err)
+    }
+}
+
+impl From<elf::ElfError> for UnpackingError {
+    fn from(err: elf::ElfError) -> Self {
+        UnpackingError::ElfParsing(err)
+    }
+}
+
+impl UnpackingError {
+    /// The 1-based line number of the record that caused the error, if known.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            UnpackingError::AtLine { line, .. } | UnpackingError::DataAfterEof { line } => {
+                Some(*line)
+            }
+            _ => None,
+        }
+    }
+
+    /// The underlying error, without any line context.
+    pub fn kind(&self) -> &UnpackingError {
+        match self {
+            UnpackingError::AtLine { error, .. } => error.kind(),
+            _ => self,
+        }
+    }
+
+    // Adds line context to the error, if the text of the record is known.
+    pub(crate) fn at_line(self, line: usize, text: Option<&str>) -> Self {
+        match text {
+            Some(text) => UnpackingError::AtLine {
+                line,
+                record: text.to_owned(),
+                error: Box::new(self),
+            },
+            None => self,
+        }
+    }
 }
 
 pub trait ReaderExt {
@@ -66,6 +409,13 @@ pub trait ReaderExt {
         self,
         base_offset: usize,
     ) -> Result<([u8; N], usize), UnpackingError>;
+    fn to_image(self, base_offset: usize) -> Result<MemoryImage, UnpackingError>;
+    fn unpack(self, options: &UnpackOptions) -> Result<Unpacked<MemoryImage>, UnpackingError>;
+    fn unpack_vec(self, options: &UnpackOptions) -> Result<Unpacked<Vec<u8>>, UnpackingError>;
+    fn unpack_array<const N: usize>(
+        self,
+        options: &UnpackOptions,
+    ) -> Result<Unpacked<[u8; N]>, UnpackingError>;
 }
 
 impl<I> ReaderExt for I
@@ -73,106 +423,126 @@ where
     I: Iterator<Item = Result<Record, ReaderError>>,
 {
     fn to_vec(
-        mut self,
+        self,
         binary_size: usize,
         base_offset: usize,
     ) -> Result<(Vec<u8>, usize), UnpackingError> {
-        let mut binary = vec![0xFF; binary_size];
-        let used_bytes = unpack_records(&mut self, &mut binary, base_offset)?;
-        Ok((binary, used_bytes))
+        let options = UnpackOptions::new()
+            .base_offset(base_offset)
+            .size_limit(binary_size);
+        let unpacked = self.unpack_vec(&options)?;
+        Ok((unpacked.data, unpacked.used_bytes))
     }
 
-    fn to_vec_minimal(mut self, base_offset: usize) -> Result<(Vec<u8>, usize), ReaderError> {
-        unpack_records_minimal(&mut self, base_offset)
+    fn to_vec_minimal(self, base_offset: usize) -> Result<(Vec<u8>, usize), ReaderError> {
+        // Start records are ignored and data below the base offset is dropped, as they always
+        // have been here, which leaves parsing as the only way to fail.
+        let records = self.filter(|rec| {
+            !matches!(
+                rec,
+                Ok(Record::StartLinearAddress(_) | Record::StartSegmentAddress { .. })
+            )
+        });
+        let options = UnpackOptions::new()
+            .base_offset(base_offset)
+            .out_of_window(OutOfWindow::Skip);
+        match records.unpack_vec(&options) {
+            Ok(unpacked) => Ok((unpacked.data, unpacked.used_bytes)),
+            Err(err) => match err.kind() {
+                UnpackingError::Parsing(err) => Err(*err),
+                err => unreachable!("unexpected error unpacking records: {}", err),
+            },
+        }
     }
 
     fn to_array<const N: usize>(
-        mut self,
+        self,
         base_offset: usize,
     ) -> Result<([u8; N], usize), UnpackingError> {
-        let mut binary = [0xFF; N];
-        let used_bytes = unpack_records(&mut self, &mut binary, base_offset)?;
-        Ok((binary, used_bytes))
+        let unpacked = self.unpack_array::<N>(&UnpackOptions::new().base_offset(base_offset))?;
+        Ok((unpacked.data, unpacked.used_bytes))
     }
-}
 
-fn unpack_records(
-    records: &mut impl Iterator<Item = Result<Record, ReaderError>>,
-    binary: &mut [u8],
-    base_offset: usize,
-) -> Result<usize, UnpackingError> {
-    let mut base_address = 0;
-    let mut used_bytes = 0;
-
-    for rec in records {
-        match rec {
-            Ok(rec) => {
-                debug!("base_address=0x{:04X} rec={:?}", base_address, rec);
-                match rec {
-                    Record::Data { offset, value } => {
-                        let end_addr = base_address + offset as usize + value.len();
-                        if end_addr > binary.len() {
-                            return Err(UnpackingError::AddressTooHigh(end_addr, binary.len()));
-                        }
-
-                        used_bytes += value.len();
-                        for (n, b) in value.iter().enumerate() {
-                            binary[base_address + offset as usize + n] = *b;
-                        }
-                    }
-                    Record::ExtendedSegmentAddress(base) => {
-                        base_address = ((base as usize) << 4) - base_offset
-                    }
-                    Record::ExtendedLinearAddress(base) => {
-                        base_address = ((base as usize) << 16) - base_offset
-                    }
-                    Record::EndOfFile => break,
-                    // Defines the start location for our program. This doesn't concern us so we
-                    // ignore it.
-                    Record::StartLinearAddress(_) | Record::StartSegmentAddress { .. } => {}
-                }
-            }
-            Err(err) => return Err(UnpackingError::Parsing(err)),
-        }
+    fn to_image(self, base_offset: usize) -> Result<MemoryImage, UnpackingError> {
+        Ok(self
+            .unpack(&UnpackOptions::new().base_offset(base_offset))?
+            .data)
+    }
+
+    fn unpack(self, options: &UnpackOptions) -> Result<Unpacked<MemoryImage>, UnpackingError> {
+        unpack_records(numbered(self), options, options.size_limit)
     }
 
-    Ok(used_bytes)
+    fn unpack_vec(self, options: &UnpackOptions) -> Result<Unpacked<Vec<u8>>, UnpackingError> {
+        Ok(unpack_records(numbered(self), options, options.size_limit)?.into_vec(options))
+    }
+
+    fn unpack_array<const N: usize>(
+        self,
+        options: &UnpackOptions,
+    ) -> Result<Unpacked<[u8; N]>, UnpackingError> {
+        let size_limit = unpack::array_size_limit::<N>(options);
+        Ok(unpack_records(numbered(self), options, size_limit)?.into_array(options))
+    }
 }
 
-fn unpack_records_minimal(
-    records: &mut impl Iterator<Item = Result<Record, ReaderError>>,
-    base_offset: usize,
-) -> Result<(Vec<u8>, usize), ReaderError> {
-    let mut binary = Vec::new();
-    let mut base_address = 0;
-    let mut used_bytes = 0;
-
-    for rec in records {
-        debug!("base_address=0x{:04X} rec={:?}", base_address, rec);
-        match rec? {
-            Record::Data { offset, value } => {
-                let end_addr = base_address + offset as usize + value.len();
-                if end_addr > binary.len() {
-                    binary.resize(end_addr, 0xFF);
-                }
+pub(crate) fn unpack_records(
+    records: impl RecordSource<Result<Record, ReaderError>>,
+    options: &UnpackOptions,
+    size_limit: Option<usize>,
+) -> Result<Unpacked<MemoryImage>, UnpackingError> {
+    let mut base_address = BaseAddress::Linear(0);
+    unpack::unpack_numbered(records, options, size_limit, |unpacker, rec| {
+        unpack_record(unpacker, &mut base_address, rec, options)
+    })
+}
+
+// The address that data record offsets are relative to, as set by the last extended address
+// record.
+#[derive(Clone, Copy, Debug)]
+enum BaseAddress {
+    Linear(usize),
+    Segment(usize),
+}
 
-                used_bytes += value.len();
-                for (n, b) in value.iter().enumerate() {
-                    binary[base_address + offset as usize + n] = *b;
+// Returns whether the record was an End Of File record.
+fn unpack_record(
+    unpacker: &mut Unpacker,
+    base_address: &mut BaseAddress,
+    rec: Result<Record, ReaderError>,
+    options: &UnpackOptions,
+) -> Result<bool, UnpackingError> {
+    let rec = rec?;
+    debug!("base_address={:X?} rec={:?}", base_address, rec);
+    match rec {
+        Record::Data { offset, value } => {
+            let offset = offset as usize;
+            match *base_address {
+                // Offsets wrap around within the segment rather than running into the next one.
+                BaseAddress::Segment(base) if options.segment_wrap => {
+                    let head_len = (0x1_0000 - offset) * options.address_unit.bytes();
+                    let (head, tail) = value.split_at(head_len.min(value.len()));
+                    unpacker.word_data(base + offset, head)?;
+                    if !tail.is_empty() {
+                        unpacker.word_data(base, tail)?;
+                    }
+                }
+                BaseAddress::Segment(base) | BaseAddress::Linear(base) => {
+                    unpacker.word_data(base + offset, &value)?
                 }
             }
-            Record::ExtendedSegmentAddress(base) => {
-                base_address = ((base as usize) << 4) - base_offset
-            }
-            Record::ExtendedLinearAddress(base) => {
-                base_address = ((base as usize) << 16) - base_offset
-            }
-            Record::EndOfFile => break,
-            // Defines the start location for our program. This doesn't concern us so we
-            // ignore it.
-            Record::StartLinearAddress(_) | Record::StartSegmentAddress { .. } => {}
+        }
+        Record::ExtendedSegmentAddress(base) => {
+            *base_address = BaseAddress::Segment((base as usize) << 4)
+        }
+        Record::ExtendedLinearAddress(base) => {
+            *base_address = BaseAddress::Linear((base as usize) << 16)
+        }
+        Record::EndOfFile => return Ok(true),
+        Record::StartLinearAddress(address) => unpacker.entry_point(EntryPoint::Linear(address))?,
+        Record::StartSegmentAddress { cs, ip } => {
+            unpacker.entry_point(EntryPoint::Segment { cs, ip })?
         }
     }
-
-    Ok((binary, used_bytes))
+    Ok(false)
 }
@@ -0,0 +1,187 @@
+use std::fmt::Write;
+use std::iter;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::{Endian, MemoryImage, SaveError, WritingError};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MemInitFormat {
+    /// Verilog `$readmemh` input.
+    ReadMemH,
+    /// Altera/Intel Memory Initialization File.
+    Mif,
+    /// Xilinx coefficient file.
+    Coe,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WordWidth {
+    Bits8,
+    Bits16,
+    Bits32,
+    Bits64,
+}
+
+impl WordWidth {
+    pub fn bytes(self) -> usize {
+        match self {
+            WordWidth::Bits8 => 1,
+            WordWidth::Bits16 => 2,
+            WordWidth::Bits32 => 4,
+            WordWidth::Bits64 => 8,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemInitOptions {
+    pub word_width: WordWidth,
+    /// The order of the bytes of the image within each word.
+    pub endian: Endian,
+    /// The number of words in the memory. Defaults to just enough to hold the image.
+    pub depth: Option<usize>,
+    /// The byte used for gaps between data.
+    pub fill: u8,
+    /// Skips over gaps with `@address` directives in `$readmemh` output, rather than writing
+    /// every word. MIF output always writes gaps as a single address range, and COE output
+    /// always writes every word.
+    pub address_directives: bool,
+}
+
+impl Default for MemInitOptions {
+    fn default() -> Self {
+        MemInitOptions {
+            word_width: WordWidth::Bits32,
+            endian: Endian::Little,
+            depth: None,
+            fill: 0x00,
+            address_directives: false,
+        }
+    }
+}
+
+struct Words<'a> {
+    image: &'a MemoryImage,
+    options: &'a MemInitOptions,
+    depth: usize,
+}
+
+impl Words<'_> {
+    fn is_set(&self, n: usize) -> bool {
+        let len = self.options.word_width.bytes();
+        self.image.overlaps(n * len, len)
+    }
+
+    fn format(&self, n: usize) -> String {
+        let len = self.options.word_width.bytes();
+        let mut bytes = [0; 8];
+        self.image
+            .copy_to_slice(n * len, &mut bytes[..len], &[self.options.fill]);
+        let bytes = &mut bytes[..len];
+        if self.options.endian == Endian::Little {
+            bytes.reverse();
+        }
+        bytes.iter().fold(String::new(), |mut acc, b| {
+            write!(acc, "{:02X}", b).unwrap();
+            acc
+        })
+    }
+
+    // Runs of consecutive words that are either all set or all gaps.
+    fn runs(&self) -> impl Iterator<Item = (bool, Range<usize>)> + '_ {
+        let mut n = 0;
+        iter::from_fn(move || {
+            if n >= self.depth {
+                return None;
+            }
+            let start = n;
+            let set = self.is_set(n);
+            while n < self.depth && self.is_set(n) == set {
+                n += 1;
+            }
+            Some((set, start..n))
+        })
+    }
+}
+
+pub fn image_to_mem_init(
+    image: &MemoryImage,
+    format: MemInitFormat,
+    options: &MemInitOptions,
+) -> Result<String, WritingError> {
+    let len = options.word_width.bytes();
+    let end = image.end_address().unwrap_or(0);
+    let depth = options.depth.unwrap_or(end.div_ceil(len));
+    // COE and MIF files can't describe an empty memory, so no format allows one.
+    if depth == 0 {
+        return Err(WritingError::ZeroDepth);
+    }
+    let size = depth
+        .checked_mul(len)
+        .ok_or(WritingError::AddressTooHigh(end, usize::MAX))?;
+    if end > size {
+        return Err(WritingError::AddressTooHigh(end, size));
+    }
+    let words = Words {
+        image,
+        options,
+        depth,
+    };
+
+    let mut out = String::new();
+    match format {
+        MemInitFormat::ReadMemH if options.address_directives => {
+            for (_, run) in words.runs().filter(|(set, _)| *set) {
+                writeln!(out, "@{:X}", run.start).unwrap();
+                for n in run {
+                    writeln!(out, "{}", words.format(n)).unwrap();
+                }
+            }
+        }
+        MemInitFormat::ReadMemH => {
+            for n in 0..depth {
+                writeln!(out, "{}", words.format(n)).unwrap();
+            }
+        }
+        MemInitFormat::Mif => {
+            writeln!(out, "WIDTH={};", len * 8).unwrap();
+            writeln!(out, "DEPTH={};", depth).unwrap();
+            writeln!(
+                out,
+                "\nADDRESS_RADIX=HEX;\nDATA_RADIX=HEX;\n\nCONTENT BEGIN"
+            )
+            .unwrap();
+            for (set, run) in words.runs() {
+                if set || run.len() == 1 {
+                    for n in run {
+                        writeln!(out, "\t{:X} : {};", n, words.format(n)).unwrap();
+                    }
+                } else {
+                    let fill = words.format(run.start);
+                    writeln!(out, "\t[{:X}..{:X}] : {};", run.start, run.end - 1, fill).unwrap();
+                }
+            }
+            writeln!(out, "END;").unwrap();
+        }
+        MemInitFormat::Coe => {
+            writeln!(out, "memory_initialization_radix=16;").unwrap();
+            writeln!(out, "memory_initialization_vector=").unwrap();
+            for n in 0..depth {
+                let end = if n + 1 == depth { ';' } else { ',' };
+                writeln!(out, "{}{}", words.format(n), end).unwrap();
+            }
+        }
+    }
+    Ok(out)
+}
+
+pub fn save_mem_init_file<P: AsRef<Path>>(
+    path: P,
+    image: &MemoryImage,
+    format: MemInitFormat,
+    options: &MemInitOptions,
+) -> Result<(), SaveError> {
+    let contents = image_to_mem_init(image, format, options)?;
+    crate::write_file(path, &contents)
+}
@@ -0,0 +1,127 @@
+use ihex::{ReaderError, Record};
+use thiserror::Error;
+
+use crate::records::numbered;
+use crate::{EntryPoint, MemoryImage, UnpackOptions, Unpacked, UnpackingError};
+
+/// An image to be combined with others by [`merge`], either as IHEX records or already
+/// unpacked from any format.
+pub struct MergeInput<'a> {
+    source: Source<'a>,
+    offset: isize,
+    keep_entry_point: bool,
+}
+
+enum Source<'a> {
+    Records(Box<dyn Iterator<Item = Result<Record, ReaderError>> + 'a>),
+    Unpacked(Unpacked<MemoryImage>),
+}
+
+impl<'a> MergeInput<'a> {
+    /// IHEX records, which are unpacked with the options given to [`merge`].
+    pub fn new<I>(records: I) -> Self
+    where
+        I: IntoIterator<Item = Result<Record, ReaderError>>,
+        I::IntoIter: 'a,
+    {
+        MergeInput::with_source(Source::Records(Box::new(records.into_iter())))
+    }
+
+    /// An image that has already been unpacked, such as by
+    /// [`load_file_auto`](crate::load_file_auto).
+    pub fn unpacked(unpacked: Unpacked<MemoryImage>) -> Self {
+        MergeInput::with_source(Source::Unpacked(unpacked))
+    }
+
+    fn with_source(source: Source<'a>) -> Self {
+        MergeInput {
+            source,
+            offset: 0,
+            keep_entry_point: true,
+        }
+    }
+
+    /// Moves the data of this input by `offset` bytes once it has been unpacked. The entry point
+    /// is left as is.
+    pub fn relocate(mut self, offset: isize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Whether to use the entry point of this input, if it has one. Defaults to true.
+    pub fn keep_entry_point(mut self, keep: bool) -> Self {
+        self.keep_entry_point = keep;
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Error)]
+#[non_exhaustive]
+pub enum MergeError {
+    #[error("Input {input}: {error}")]
+    Unpacking { input: usize, error: UnpackingError },
+    #[error("Input {input} was relocated outside of the address space")]
+    RelocationOverflow { input: usize },
+    #[error("Input {input} conflicts with earlier inputs at address ({address:#X})")]
+    Conflict { input: usize, address: usize },
+    #[error("Input {input} entry point ({new:X?}) conflicts with earlier entry point ({old:X?})")]
+    ConflictingEntryPoint {
+        input: usize,
+        old: EntryPoint,
+        new: EntryPoint,
+    },
+}
+
+/// Unpacks each record input with `options` and combines them into one image. Inputs may overlap as
+/// long as they agree on the overlapping bytes, and only one entry point may be given.
+pub fn merge<'a>(
+    inputs: impl IntoIterator<Item = MergeInput<'a>>,
+    options: &UnpackOptions,
+) -> Result<Unpacked<MemoryImage>, MergeError> {
+    let mut merged = Unpacked {
+        data: MemoryImage::new(),
+        used_bytes: 0,
+        entry_point: None,
+        out_of_window: MemoryImage::new(),
+    };
+
+    for (input, merge_input) in inputs.into_iter().enumerate() {
+        let unpacked = match merge_input.source {
+            Source::Records(records) => {
+                crate::unpack_records(numbered(records), options, options.size_limit)
+                    .map_err(|error| MergeError::Unpacking { input, error })?
+            }
+            Source::Unpacked(unpacked) => unpacked,
+        };
+        let relocate = |image: &MemoryImage| {
+            image
+                .relocated(merge_input.offset)
+                .ok_or(MergeError::RelocationOverflow { input })
+        };
+        let image = relocate(&unpacked.data)?;
+
+        if let Some(address) = merged.data.first_conflict(&image) {
+            return Err(MergeError::Conflict { input, address });
+        }
+        for segment in image.segments() {
+            merged.data.write(segment.address(), segment.data());
+        }
+        for segment in relocate(&unpacked.out_of_window)?.segments() {
+            merged
+                .out_of_window
+                .write(segment.address(), segment.data());
+        }
+
+        match (merged.entry_point, unpacked.entry_point) {
+            (_, None) => {}
+            _ if !merge_input.keep_entry_point => {}
+            (Some(old), Some(new)) if old != new => {
+                return Err(MergeError::ConflictingEntryPoint { input, old, new });
+            }
+            (_, new) => merged.entry_point = new,
+        }
+    }
+
+    merged.used_bytes = merged.data.len();
+    Ok(merged)
+}
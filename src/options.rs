@@ -0,0 +1,167 @@
+use crate::Endian;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverlapPolicy {
+    /// Fail on any data record that overlaps earlier data.
+    Error,
+    /// Later data records overwrite earlier ones.
+    #[default]
+    LastWins,
+    /// Earlier data records take precedence over later ones.
+    FirstWins,
+    /// Allow overlapping data records only if they agree on the overlapping bytes.
+    ErrorIfDifferent,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutOfWindow {
+    /// Fail on any data below the base offset or beyond the size limit.
+    #[default]
+    Error,
+    /// Silently drop data outside of the window.
+    Skip,
+    /// Drop data outside of the window from the image, but keep it aside in
+    /// [`Unpacked::out_of_window`](crate::Unpacked::out_of_window).
+    Collect,
+}
+
+/// The size of the memory location each address in a file refers to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum AddressUnit {
+    #[default]
+    Byte,
+    /// 16-bit words, as in INHX16 files from PIC and DSP toolchains.
+    Word16,
+    Word32,
+}
+
+impl AddressUnit {
+    pub fn bytes(self) -> usize {
+        match self {
+            AddressUnit::Byte => 1,
+            AddressUnit::Word16 => 2,
+            AddressUnit::Word32 => 4,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnpackOptions {
+    pub(crate) fill: Vec<u8>,
+    pub(crate) base_offset: usize,
+    pub(crate) size_limit: Option<usize>,
+    pub(crate) overlap: OverlapPolicy,
+    pub(crate) out_of_window: OutOfWindow,
+    pub(crate) strict_eof: bool,
+    pub(crate) address_unit: AddressUnit,
+    pub(crate) word_endian: Endian,
+    pub(crate) segment_wrap: bool,
+    pub(crate) zero_entry_point: bool,
+}
+
+impl Default for UnpackOptions {
+    fn default() -> Self {
+        UnpackOptions {
+            fill: vec![0xFF],
+            base_offset: 0,
+            size_limit: None,
+            overlap: OverlapPolicy::default(),
+            out_of_window: OutOfWindow::default(),
+            strict_eof: false,
+            address_unit: AddressUnit::default(),
+            word_endian: Endian::default(),
+            segment_wrap: true,
+            zero_entry_point: false,
+        }
+    }
+}
+
+impl UnpackOptions {
+    pub fn new() -> Self {
+        UnpackOptions::default()
+    }
+
+    /// Fills gaps between data with `fill`. Defaults to 0xFF.
+    pub fn fill_byte(self, fill: u8) -> Self {
+        self.fill_pattern(&[fill])
+    }
+
+    /// Fills gaps between data with a repeating `pattern`, aligned so that the byte at address
+    /// `n` is `pattern[n % pattern.len()]`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is empty.
+    pub fn fill_pattern(mut self, pattern: &[u8]) -> Self {
+        assert!(!pattern.is_empty(), "fill pattern must not be empty");
+        self.fill = pattern.to_vec();
+        self
+    }
+
+    /// Subtracts `base_offset` from every address in the file.
+    pub fn base_offset(mut self, base_offset: usize) -> Self {
+        self.base_offset = base_offset;
+        self
+    }
+
+    /// Fails if any data ends beyond `size_limit`, after the base offset is applied.
+    pub fn size_limit(mut self, size_limit: usize) -> Self {
+        self.size_limit = Some(size_limit);
+        self
+    }
+
+    pub fn overlap(mut self, overlap: OverlapPolicy) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    /// How to handle data below the base offset or beyond the size limit.
+    pub fn out_of_window(mut self, out_of_window: OutOfWindow) -> Self {
+        self.out_of_window = out_of_window;
+        self
+    }
+
+    /// Fails if the records end without an End Of File record, or if anything other than
+    /// whitespace follows it.
+    ///
+    /// Data after the End Of File record is only detected when loading from a file or reader,
+    /// such as with [`load_file_image`](crate::load_file_image). `ihex::Reader` and the readers
+    /// of the other formats stop at the end record, so unpacking through
+    /// [`ReaderExt`](crate::ReaderExt) and friends only checks that the record is present.
+    pub fn strict_eof(mut self, strict_eof: bool) -> Self {
+        self.strict_eof = strict_eof;
+        self
+    }
+
+    /// Whether data records after an Extended Segment Address record wrap around to the start of
+    /// their 64KiB segment, as the I16HEX spec requires, rather than running on into the next
+    /// one. Defaults to true.
+    pub fn segment_wrap(mut self, segment_wrap: bool) -> Self {
+        self.segment_wrap = segment_wrap;
+        self
+    }
+
+    /// Whether an entry point of zero from an S-record termination record or an ELF header is
+    /// kept, rather than meaning there is no entry point. S-record files always end in a
+    /// termination record and ELF headers always have an entry field, so both use zero when
+    /// there is no entry point, but parts that reset to address zero need it. IHEX start address
+    /// records are always kept. Defaults to false.
+    pub fn zero_entry_point(mut self, zero_entry_point: bool) -> Self {
+        self.zero_entry_point = zero_entry_point;
+        self
+    }
+
+    /// Treats IHEX addresses as addresses of `unit` sized words, so that each is multiplied by
+    /// the word size to get the byte address. The base offset and size limit stay in bytes.
+    pub fn address_unit(mut self, unit: AddressUnit) -> Self {
+        self.address_unit = unit;
+        self
+    }
+
+    /// The order of the bytes of each word in the records when using word addresses. Words are
+    /// always unpacked in little endian order. Defaults to little endian.
+    pub fn word_endian(mut self, endian: Endian) -> Self {
+        self.word_endian = endian;
+        self
+    }
+}
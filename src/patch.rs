@@ -0,0 +1,103 @@
+use std::ops::Range;
+
+use thiserror::Error;
+
+use crate::{Checksum, CrcParams, Digest, GapPolicy, MemoryImage};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Endian {
+    #[default]
+    Little,
+    Big,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Error)]
+#[non_exhaustive]
+pub enum PatchError {
+    #[error("Patch at address ({address:#X}) of length ({len}) overlaps existing data")]
+    Overlap { address: usize, len: usize },
+    #[error("Patch at address ({0:#X}) does not fit in the address space")]
+    AddressTooHigh(usize),
+    #[error("CRC at address ({address:#X}) lies within the range it covers")]
+    CrcInRange { address: usize },
+}
+
+/// Writes values into a memory image, such as version stamps or serial numbers, before it is
+/// saved again. Refuses to overwrite data already in the image unless allowed.
+pub struct Patcher<'a> {
+    image: &'a mut MemoryImage,
+    allow_overwrite: bool,
+}
+
+impl MemoryImage {
+    pub fn patcher(&mut self) -> Patcher<'_> {
+        Patcher {
+            image: self,
+            allow_overwrite: false,
+        }
+    }
+}
+
+impl<'a> Patcher<'a> {
+    pub fn allow_overwrite(mut self, allow: bool) -> Self {
+        self.allow_overwrite = allow;
+        self
+    }
+
+    pub fn bytes(&mut self, address: usize, data: &[u8]) -> Result<(), PatchError> {
+        address
+            .checked_add(data.len())
+            .ok_or(PatchError::AddressTooHigh(address))?;
+        if !self.allow_overwrite && self.image.overlaps(address, data.len()) {
+            return Err(PatchError::Overlap {
+                address,
+                len: data.len(),
+            });
+        }
+        self.image.write(address, data);
+        Ok(())
+    }
+
+    pub fn u16(&mut self, address: usize, value: u16, endian: Endian) -> Result<(), PatchError> {
+        match endian {
+            Endian::Little => self.bytes(address, &value.to_le_bytes()),
+            Endian::Big => self.bytes(address, &value.to_be_bytes()),
+        }
+    }
+
+    pub fn u32(&mut self, address: usize, value: u32, endian: Endian) -> Result<(), PatchError> {
+        match endian {
+            Endian::Little => self.bytes(address, &value.to_le_bytes()),
+            Endian::Big => self.bytes(address, &value.to_be_bytes()),
+        }
+    }
+
+    /// Computes the CRC of `range` and writes it at `address`, taking as many bytes as the CRC
+    /// width needs. Returns the CRC.
+    pub fn crc(
+        &mut self,
+        address: usize,
+        range: Range<usize>,
+        params: CrcParams,
+        gaps: GapPolicy,
+        endian: Endian,
+    ) -> Result<u32, PatchError> {
+        let len = (params.width() as usize).div_ceil(8);
+        if address < range.end && range.start < address.saturating_add(len) {
+            return Err(PatchError::CrcInRange { address });
+        }
+
+        let Digest::Crc(crc) = self.image.checksum(range, Checksum::Crc(params), gaps) else {
+            unreachable!()
+        };
+        let bytes = match endian {
+            Endian::Little => crc.to_le_bytes(),
+            Endian::Big => crc.to_be_bytes(),
+        };
+        match endian {
+            Endian::Little => self.bytes(address, &bytes[..len])?,
+            Endian::Big => self.bytes(address, &bytes[4 - len..])?,
+        }
+        Ok(crc)
+    }
+}
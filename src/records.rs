@@ -0,0 +1,214 @@
+use std::io::BufRead;
+use std::str;
+
+use crate::LoadError;
+
+// A parsed record along with the 1-based line it came from.
+pub(crate) struct Numbered<T> {
+    pub(crate) line: usize,
+    pub(crate) record: T,
+}
+
+// An iterator of numbered records that can also give the text of the last record it returned,
+// so that the text only needs to be copied when reporting an error. The text is only known when
+// reading from text, rather than from an arbitrary iterator of records.
+pub(crate) trait RecordSource<T>: Iterator<Item = Numbered<T>> {
+    fn text(&self) -> Option<&str>;
+}
+
+impl<T, S: RecordSource<T> + ?Sized> RecordSource<T> for &mut S {
+    fn text(&self) -> Option<&str> {
+        (**self).text()
+    }
+}
+
+// Numbers records from a plain iterator by their position, as there is no way to know which line
+// they originally came from.
+pub(crate) struct Enumerated<I> {
+    records: std::iter::Enumerate<I>,
+}
+
+pub(crate) fn numbered<I: Iterator>(records: I) -> Enumerated<I> {
+    Enumerated {
+        records: records.enumerate(),
+    }
+}
+
+impl<I: Iterator> Iterator for Enumerated<I> {
+    type Item = Numbered<I::Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (n, record) = self.records.next()?;
+        Some(Numbered {
+            line: n + 1,
+            record,
+        })
+    }
+}
+
+impl<I: Iterator> RecordSource<I::Item> for Enumerated<I> {
+    fn text(&self) -> Option<&str> {
+        None
+    }
+}
+
+// Parses the non-blank lines of a string as records, stopping after the first error or the first
+// record for which `is_end` is true, in the same way as `ihex::Reader`.
+pub(crate) struct LineReader<'a, R, E> {
+    lines: str::Lines<'a>,
+    finished: bool,
+    parse: fn(&str) -> Result<R, E>,
+    is_end: fn(&R) -> bool,
+}
+
+impl<'a, R, E> LineReader<'a, R, E> {
+    pub(crate) fn new(
+        string: &'a str,
+        parse: fn(&str) -> Result<R, E>,
+        is_end: fn(&R) -> bool,
+    ) -> Self {
+        LineReader {
+            lines: string.lines(),
+            finished: false,
+            parse,
+            is_end,
+        }
+    }
+}
+
+impl<R, E> Iterator for LineReader<'_, R, E> {
+    type Item = Result<R, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let line = self.lines.find(|line| !line.trim().is_empty());
+        let result = line.map(|line| (self.parse)(line.trim()));
+        if !matches!(&result, Some(Ok(rec)) if !(self.is_end)(rec)) {
+            self.finished = true;
+        }
+        result
+    }
+}
+
+// Iterates over the records in a string along with their 1-based line numbers, skipping blank
+// lines. Unlike `ihex::Reader` this keeps going after an End Of File record, so that any trailing
+// data can be reported.
+pub(crate) struct Records<'a, T> {
+    lines: str::Lines<'a>,
+    line: usize,
+    text: &'a str,
+    parse: fn(&str) -> T,
+}
+
+impl<'a, T> Records<'a, T> {
+    pub(crate) fn new(string: &'a str, parse: fn(&str) -> T) -> Self {
+        Records {
+            lines: string.lines(),
+            line: 0,
+            text: "",
+            parse,
+        }
+    }
+}
+
+impl<T> Iterator for Records<'_, T> {
+    type Item = Numbered<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for line in self.lines.by_ref() {
+            self.line += 1;
+            let line = line.trim();
+            if !line.is_empty() {
+                self.text = line;
+                return Some(Numbered {
+                    line: self.line,
+                    record: (self.parse)(line),
+                });
+            }
+        }
+        None
+    }
+}
+
+impl<T> RecordSource<T> for Records<'_, T> {
+    fn text(&self) -> Option<&str> {
+        Some(self.text)
+    }
+}
+
+// Like `Records`, but reads lines incrementally from a `BufRead`. Iteration stops at the first IO
+// error or non-text byte, which is kept in `error` to be checked once unpacking is done.
+pub(crate) struct ReaderRecords<R, T> {
+    reader: R,
+    line: usize,
+    offset: usize,
+    buf: Vec<u8>,
+    parse: fn(&str) -> T,
+    pub(crate) error: Option<LoadError>,
+}
+
+impl<R: BufRead, T> ReaderRecords<R, T> {
+    pub(crate) fn new(reader: R, parse: fn(&str) -> T) -> Self {
+        ReaderRecords {
+            reader,
+            line: 0,
+            offset: 0,
+            buf: Vec::new(),
+            parse,
+            error: None,
+        }
+    }
+
+    // The text of the line in the buffer, which is only ever read once checked to be ASCII.
+    fn line_text(&self) -> &str {
+        str::from_utf8(&self.buf).unwrap().trim()
+    }
+}
+
+impl<R: BufRead, T> Iterator for ReaderRecords<R, T> {
+    type Item = Numbered<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buf.clear();
+            match self.reader.read_until(b'\n', &mut self.buf) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(err) => {
+                    self.error = Some(LoadError::FailedRead(err));
+                    return None;
+                }
+            }
+            self.line += 1;
+
+            if let Some(pos) = self.buf.iter().position(|b| !is_text(*b)) {
+                self.error = Some(LoadError::NotText {
+                    offset: self.offset + pos,
+                });
+                return None;
+            }
+            self.offset += self.buf.len();
+
+            let line = self.line_text();
+            if !line.is_empty() {
+                return Some(Numbered {
+                    line: self.line,
+                    record: (self.parse)(line),
+                });
+            }
+        }
+    }
+}
+
+impl<R: BufRead, T> RecordSource<T> for ReaderRecords<R, T> {
+    fn text(&self) -> Option<&str> {
+        Some(self.line_text())
+    }
+}
+
+pub(crate) fn is_text(b: u8) -> bool {
+    b.is_ascii_graphic() || b.is_ascii_whitespace()
+}
@@ -0,0 +1,438 @@
+use std::path::Path;
+use std::str;
+
+use log::*;
+use thiserror::Error;
+
+use crate::records::{numbered, LineReader, RecordSource, Records};
+use crate::unpack::{self, Unpacker};
+use crate::writer;
+use crate::{
+    EntryPoint, LoadError, MemoryImage, SaveError, UnpackOptions, Unpacked, UnpackingError,
+    WritingError,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SRecord {
+    /// S0: Vendor specific header data, usually a module name.
+    Header(Vec<u8>),
+    /// S1, S2 or S3: Data at a 16, 24 or 32-bit address.
+    Data { address: u32, value: Vec<u8> },
+    /// S5 or S6: The number of data records preceding this record.
+    Count(u32),
+    /// S7, S8 or S9: The execution start address, terminating the file.
+    Start(u32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AddressWidth {
+    Bits16,
+    Bits24,
+    Bits32,
+}
+
+impl AddressWidth {
+    fn bytes(self) -> usize {
+        match self {
+            AddressWidth::Bits16 => 2,
+            AddressWidth::Bits24 => 3,
+            AddressWidth::Bits32 => 4,
+        }
+    }
+
+    fn limit(self) -> u64 {
+        1 << (self.bytes() * 8)
+    }
+
+    fn for_address(address: usize) -> Self {
+        if address <= 0xFFFF {
+            AddressWidth::Bits16
+        } else if address <= 0xFF_FFFF {
+            AddressWidth::Bits24
+        } else {
+            AddressWidth::Bits32
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Error)]
+#[non_exhaustive]
+pub enum SrecError {
+    #[error("missing start code 'S'")]
+    MissingStartCode,
+    #[error("too short")]
+    RecordTooShort,
+    #[error("record does not contain a whole number of bytes")]
+    RecordNotEvenLength,
+    #[error("invalid characters encountered in record")]
+    ContainsInvalidCharacters,
+    #[error("invalid checksum '{0:02X}', expecting '{1:02X}'")]
+    ChecksumMismatch(u8, u8),
+    #[error("payload length does not match record header")]
+    PayloadLengthMismatch,
+    #[error("unsupported record type 'S{0}'")]
+    UnsupportedRecordType(char),
+    #[error("record count ({0}) does not match number of data records ({1})")]
+    CountMismatch(u32, u32),
+}
+
+impl SRecord {
+    pub fn from_record_string(string: &str) -> Result<Self, SrecError> {
+        let rest = string
+            .strip_prefix('S')
+            .ok_or(SrecError::MissingStartCode)?;
+        let mut chars = rest.chars();
+        let record_type = chars.next().ok_or(SrecError::RecordTooShort)?;
+        let hex = chars.as_str();
+
+        if hex.len() % 2 != 0 {
+            return Err(SrecError::RecordNotEvenLength);
+        }
+        let bytes = hex
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| {
+                str::from_utf8(pair)
+                    .ok()
+                    .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                    .ok_or(SrecError::ContainsInvalidCharacters)
+            })
+            .collect::<Result<Vec<u8>, _>>()?;
+
+        let address_len = match record_type {
+            '0' | '1' | '5' | '9' => 2,
+            '2' | '6' | '8' => 3,
+            '3' | '7' => 4,
+            _ => return Err(SrecError::UnsupportedRecordType(record_type)),
+        };
+        let (&count, payload) = bytes.split_first().ok_or(SrecError::RecordTooShort)?;
+        if payload.len() != count as usize {
+            return Err(SrecError::PayloadLengthMismatch);
+        }
+        if payload.len() < address_len + 1 {
+            return Err(SrecError::RecordTooShort);
+        }
+
+        let (&found, payload) = payload.split_last().unwrap();
+        let expecting = checksum(count, payload);
+        if found != expecting {
+            return Err(SrecError::ChecksumMismatch(found, expecting));
+        }
+
+        let (address, value) = payload.split_at(address_len);
+        let address = address.iter().fold(0u32, |acc, b| (acc << 8) | *b as u32);
+        Ok(match record_type {
+            '0' => SRecord::Header(value.to_vec()),
+            '1' | '2' | '3' => SRecord::Data {
+                address,
+                value: value.to_vec(),
+            },
+            '5' | '6' => SRecord::Count(address),
+            _ => SRecord::Start(address),
+        })
+    }
+
+    /// Returns the S-record representation, using `width` for the address of data and start
+    /// records.
+    pub fn to_record_string(&self, width: AddressWidth) -> String {
+        let (record_type, address, address_len, value): (u8, u32, usize, &[u8]) = match self {
+            SRecord::Header(value) => (0, 0, 2, value),
+            SRecord::Data { address, value } => {
+                (width.bytes() as u8 - 1, *address, width.bytes(), value)
+            }
+            SRecord::Count(count) => {
+                let width = AddressWidth::for_address(*count as usize);
+                (width.bytes() as u8 + 3, *count, width.bytes(), &[])
+            }
+            SRecord::Start(address) => (11 - width.bytes() as u8, *address, width.bytes(), &[]),
+        };
+
+        let mut payload = address.to_be_bytes()[4 - address_len..].to_vec();
+        payload.extend_from_slice(value);
+        let count = payload.len() as u8 + 1;
+
+        let mut string = format!("S{}{:02X}", record_type, count);
+        for b in &payload {
+            string.push_str(&format!("{:02X}", b));
+        }
+        string.push_str(&format!("{:02X}", checksum(count, &payload)));
+        string
+    }
+}
+
+fn checksum(count: u8, payload: &[u8]) -> u8 {
+    !payload.iter().fold(count, |acc, b| acc.wrapping_add(*b))
+}
+
+pub struct SrecReader<'a>(LineReader<'a, SRecord, SrecError>);
+
+impl<'a> SrecReader<'a> {
+    pub fn new(string: &'a str) -> Self {
+        SrecReader(LineReader::new(
+            string,
+            SRecord::from_record_string,
+            |rec| matches!(rec, SRecord::Start(_)),
+        ))
+    }
+}
+
+impl Iterator for SrecReader<'_> {
+    type Item = Result<SRecord, SrecError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+pub trait SrecReaderExt {
+    fn to_vec(
+        self,
+        binary_size: usize,
+        base_offset: usize,
+    ) -> Result<(Vec<u8>, usize), UnpackingError>;
+    fn to_vec_minimal(self, base_offset: usize) -> Result<(Vec<u8>, usize), UnpackingError>;
+    fn unpack(self, options: &UnpackOptions) -> Result<Unpacked<MemoryImage>, UnpackingError>;
+    fn unpack_vec(self, options: &UnpackOptions) -> Result<Unpacked<Vec<u8>>, UnpackingError>;
+    fn unpack_array<const N: usize>(
+        self,
+        options: &UnpackOptions,
+    ) -> Result<Unpacked<[u8; N]>, UnpackingError>;
+}
+
+impl<I> SrecReaderExt for I
+where
+    I: Iterator<Item = Result<SRecord, SrecError>>,
+{
+    fn to_vec(
+        self,
+        binary_size: usize,
+        base_offset: usize,
+    ) -> Result<(Vec<u8>, usize), UnpackingError> {
+        let options = UnpackOptions::new()
+            .base_offset(base_offset)
+            .size_limit(binary_size);
+        let unpacked = self.unpack_vec(&options)?;
+        Ok((unpacked.data, unpacked.used_bytes))
+    }
+
+    fn to_vec_minimal(self, base_offset: usize) -> Result<(Vec<u8>, usize), UnpackingError> {
+        let unpacked = self.unpack_vec(&UnpackOptions::new().base_offset(base_offset))?;
+        Ok((unpacked.data, unpacked.used_bytes))
+    }
+
+    fn unpack(self, options: &UnpackOptions) -> Result<Unpacked<MemoryImage>, UnpackingError> {
+        unpack_srec_records(numbered(self), options, options.size_limit)
+    }
+
+    fn unpack_vec(self, options: &UnpackOptions) -> Result<Unpacked<Vec<u8>>, UnpackingError> {
+        Ok(unpack_srec_records(numbered(self), options, options.size_limit)?.into_vec(options))
+    }
+
+    fn unpack_array<const N: usize>(
+        self,
+        options: &UnpackOptions,
+    ) -> Result<Unpacked<[u8; N]>, UnpackingError> {
+        let size_limit = unpack::array_size_limit::<N>(options);
+        Ok(unpack_srec_records(numbered(self), options, size_limit)?.into_array(options))
+    }
+}
+
+pub(crate) fn srec_records(string: &str) -> Records<'_, Result<SRecord, SrecError>> {
+    Records::new(string, SRecord::from_record_string)
+}
+
+pub(crate) fn unpack_srec_records(
+    records: impl RecordSource<Result<SRecord, SrecError>>,
+    options: &UnpackOptions,
+    size_limit: Option<usize>,
+) -> Result<Unpacked<MemoryImage>, UnpackingError> {
+    let mut data_records = 0;
+    unpack::unpack_numbered(records, options, size_limit, |unpacker, rec| {
+        unpack_srec_record(unpacker, &mut data_records, rec, options)
+    })
+}
+
+// Returns whether the record was a termination record.
+fn unpack_srec_record(
+    unpacker: &mut Unpacker,
+    data_records: &mut u32,
+    rec: Result<SRecord, SrecError>,
+    options: &UnpackOptions,
+) -> Result<bool, UnpackingError> {
+    let rec = rec?;
+    debug!("rec={:?}", rec);
+    match rec {
+        SRecord::Header(_) => {}
+        SRecord::Data { address, value } => {
+            *data_records += 1;
+            unpacker.data(address as usize, &value)?;
+        }
+        SRecord::Count(count) => {
+            if count != *data_records {
+                return Err(SrecError::CountMismatch(count, *data_records).into());
+            }
+        }
+        SRecord::Start(address) => {
+            if address != 0 || options.zero_entry_point {
+                unpacker.entry_point(EntryPoint::Linear(address))?;
+            }
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+pub fn load_srec_file_vec<P: AsRef<Path>>(
+    path: P,
+    binary_size: usize,
+    base_offset: usize,
+) -> Result<(Vec<u8>, usize), LoadError> {
+    let options = UnpackOptions::new()
+        .base_offset(base_offset)
+        .size_limit(binary_size);
+    let unpacked = load_srec_file_vec_with(path, &options)?;
+    Ok((unpacked.data, unpacked.used_bytes))
+}
+
+pub fn load_srec_file_vec_with<P: AsRef<Path>>(
+    path: P,
+    options: &UnpackOptions,
+) -> Result<Unpacked<Vec<u8>>, LoadError> {
+    Ok(load_srec_file_image(path, options)?.into_vec(options))
+}
+
+pub fn load_srec_file_image<P: AsRef<Path>>(
+    path: P,
+    options: &UnpackOptions,
+) -> Result<Unpacked<MemoryImage>, LoadError> {
+    let path = path.as_ref();
+    crate::in_file(path, || {
+        let reader = crate::open_file(path)?;
+        crate::unpack_text(reader, SRecord::from_record_string, |records| {
+            unpack_srec_records(records, options, options.size_limit)
+        })
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SrecWriteOptions {
+    /// The maximum number of data bytes in a single data record.
+    pub record_len: u8,
+    /// The address width of data records. Picks the smallest width that fits all addresses if
+    /// `None`.
+    pub address_width: Option<AddressWidth>,
+    /// The contents of the S0 header record. No header is written if empty.
+    pub header: Vec<u8>,
+    /// Whether to write an S5/S6 record count record.
+    pub count_record: bool,
+    /// The address of the termination record, which is zero if `None`.
+    pub entry_point: Option<u32>,
+}
+
+impl Default for SrecWriteOptions {
+    fn default() -> Self {
+        SrecWriteOptions {
+            record_len: 32,
+            address_width: None,
+            header: Vec::new(),
+            count_record: true,
+            entry_point: None,
+        }
+    }
+}
+
+pub fn image_to_srec_records(
+    image: &MemoryImage,
+    base_address: usize,
+    options: &SrecWriteOptions,
+) -> Result<(Vec<SRecord>, AddressWidth), WritingError> {
+    let image_end = image.end_address().unwrap_or(0);
+    let width = options.address_width.unwrap_or_else(|| {
+        let highest = base_address
+            .saturating_add(image_end)
+            .saturating_sub(1)
+            .max(options.entry_point.unwrap_or(0) as usize);
+        AddressWidth::for_address(highest)
+    });
+    writer::check_end(base_address, image_end, width.limit())?;
+    if let Some(entry_point) = options.entry_point {
+        // Only narrower widths than 32 bits can fail here, so the limit fits in a `usize`.
+        if entry_point as u64 >= width.limit() {
+            return Err(WritingError::AddressTooHigh(
+                entry_point as usize,
+                width.limit() as usize,
+            ));
+        }
+    }
+    if options.record_len == 0 || options.record_len as usize > 0xFF - width.bytes() - 1 {
+        return Err(WritingError::InvalidRecordLength(options.record_len));
+    }
+
+    // The header shares the record with a 2 byte address and a checksum.
+    if options.header.len() > 0xFF - 3 {
+        return Err(WritingError::HeaderTooLong(options.header.len(), 0xFF - 3));
+    }
+
+    let mut records = Vec::new();
+    if !options.header.is_empty() {
+        records.push(SRecord::Header(options.header.clone()));
+    }
+
+    for segment in image.segments() {
+        let mut address = base_address + segment.address();
+        for chunk in segment.data().chunks(options.record_len as usize) {
+            records.push(SRecord::Data {
+                address: address as u32,
+                value: chunk.to_vec(),
+            });
+            address += chunk.len();
+        }
+    }
+
+    if options.count_record {
+        let data_records = records.len() - !options.header.is_empty() as usize;
+        if data_records <= 0xFF_FFFF {
+            records.push(SRecord::Count(data_records as u32));
+        }
+    }
+    records.push(SRecord::Start(options.entry_point.unwrap_or(0)));
+
+    Ok((records, width))
+}
+
+pub fn image_to_srec_string(
+    image: &MemoryImage,
+    base_address: usize,
+    options: &SrecWriteOptions,
+) -> Result<String, WritingError> {
+    let (records, width) = image_to_srec_records(image, base_address, options)?;
+    Ok(records.iter().fold(String::new(), |mut acc, record| {
+        acc.push_str(&record.to_record_string(width));
+        acc.push('\n');
+        acc
+    }))
+}
+
+pub fn save_srec_file<P: AsRef<Path>>(
+    path: P,
+    binary: &[u8],
+    base_address: usize,
+    options: &SrecWriteOptions,
+) -> Result<(), SaveError> {
+    save_srec_file_image(
+        path,
+        &MemoryImage::from_slice(0, binary),
+        base_address,
+        options,
+    )
+}
+
+pub fn save_srec_file_image<P: AsRef<Path>>(
+    path: P,
+    image: &MemoryImage,
+    base_address: usize,
+    options: &SrecWriteOptions,
+) -> Result<(), SaveError> {
+    let srec = image_to_srec_string(image, base_address, options)?;
+    crate::write_file(path, &srec)
+}
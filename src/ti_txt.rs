@@ -0,0 +1,281 @@
+use std::path::Path;
+
+use log::*;
+use thiserror::Error;
+
+use crate::records::{numbered, LineReader, RecordSource, Records};
+use crate::unpack::{self, Unpacker};
+use crate::writer;
+use crate::{
+    LoadError, MemoryImage, SaveError, UnpackOptions, Unpacked, UnpackingError, WritingError,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TiTxtRecord {
+    /// `@ADDR`: The address of the data on the following lines.
+    Address(u32),
+    /// A line of space separated data bytes.
+    Data(Vec<u8>),
+    /// `q`: The end of the file.
+    End,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Error)]
+#[non_exhaustive]
+pub enum TiTxtError {
+    #[error("invalid address")]
+    InvalidAddress,
+    #[error("invalid characters encountered in data")]
+    ContainsInvalidCharacters,
+    #[error("data before the first address")]
+    MissingAddress,
+}
+
+impl TiTxtRecord {
+    pub fn from_record_string(string: &str) -> Result<Self, TiTxtError> {
+        if let Some(address) = string.strip_prefix('@') {
+            return u32::from_str_radix(address.trim(), 16)
+                .map(TiTxtRecord::Address)
+                .map_err(|_| TiTxtError::InvalidAddress);
+        }
+        if string.eq_ignore_ascii_case("q") {
+            return Ok(TiTxtRecord::End);
+        }
+
+        string
+            .split_ascii_whitespace()
+            .map(|byte| {
+                if byte.len() != 2 {
+                    return Err(TiTxtError::ContainsInvalidCharacters);
+                }
+                u8::from_str_radix(byte, 16).map_err(|_| TiTxtError::ContainsInvalidCharacters)
+            })
+            .collect::<Result<Vec<u8>, _>>()
+            .map(TiTxtRecord::Data)
+    }
+
+    pub fn to_record_string(&self) -> String {
+        match self {
+            TiTxtRecord::Address(address) => format!("@{:04X}", address),
+            TiTxtRecord::Data(value) => value
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect::<Vec<_>>()
+                .join(" "),
+            TiTxtRecord::End => "q".to_owned(),
+        }
+    }
+}
+
+pub struct TiTxtReader<'a>(LineReader<'a, TiTxtRecord, TiTxtError>);
+
+impl<'a> TiTxtReader<'a> {
+    pub fn new(string: &'a str) -> Self {
+        TiTxtReader(LineReader::new(
+            string,
+            TiTxtRecord::from_record_string,
+            |rec| *rec == TiTxtRecord::End,
+        ))
+    }
+}
+
+impl Iterator for TiTxtReader<'_> {
+    type Item = Result<TiTxtRecord, TiTxtError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+pub trait TiTxtReaderExt {
+    fn to_vec(
+        self,
+        binary_size: usize,
+        base_offset: usize,
+    ) -> Result<(Vec<u8>, usize), UnpackingError>;
+    fn to_vec_minimal(self, base_offset: usize) -> Result<(Vec<u8>, usize), UnpackingError>;
+    fn unpack(self, options: &UnpackOptions) -> Result<Unpacked<MemoryImage>, UnpackingError>;
+    fn unpack_vec(self, options: &UnpackOptions) -> Result<Unpacked<Vec<u8>>, UnpackingError>;
+    fn unpack_array<const N: usize>(
+        self,
+        options: &UnpackOptions,
+    ) -> Result<Unpacked<[u8; N]>, UnpackingError>;
+}
+
+impl<I> TiTxtReaderExt for I
+where
+    I: Iterator<Item = Result<TiTxtRecord, TiTxtError>>,
+{
+    fn to_vec(
+        self,
+        binary_size: usize,
+        base_offset: usize,
+    ) -> Result<(Vec<u8>, usize), UnpackingError> {
+        let options = UnpackOptions::new()
+            .base_offset(base_offset)
+            .size_limit(binary_size);
+        let unpacked = self.unpack_vec(&options)?;
+        Ok((unpacked.data, unpacked.used_bytes))
+    }
+
+    fn to_vec_minimal(self, base_offset: usize) -> Result<(Vec<u8>, usize), UnpackingError> {
+        let unpacked = self.unpack_vec(&UnpackOptions::new().base_offset(base_offset))?;
+        Ok((unpacked.data, unpacked.used_bytes))
+    }
+
+    fn unpack(self, options: &UnpackOptions) -> Result<Unpacked<MemoryImage>, UnpackingError> {
+        unpack_ti_txt_records(numbered(self), options, options.size_limit)
+    }
+
+    fn unpack_vec(self, options: &UnpackOptions) -> Result<Unpacked<Vec<u8>>, UnpackingError> {
+        Ok(unpack_ti_txt_records(numbered(self), options, options.size_limit)?.into_vec(options))
+    }
+
+    fn unpack_array<const N: usize>(
+        self,
+        options: &UnpackOptions,
+    ) -> Result<Unpacked<[u8; N]>, UnpackingError> {
+        let size_limit = unpack::array_size_limit::<N>(options);
+        Ok(unpack_ti_txt_records(numbered(self), options, size_limit)?.into_array(options))
+    }
+}
+
+pub(crate) fn ti_txt_records(string: &str) -> Records<'_, Result<TiTxtRecord, TiTxtError>> {
+    Records::new(string, TiTxtRecord::from_record_string)
+}
+
+pub(crate) fn unpack_ti_txt_records(
+    records: impl RecordSource<Result<TiTxtRecord, TiTxtError>>,
+    options: &UnpackOptions,
+    size_limit: Option<usize>,
+) -> Result<Unpacked<MemoryImage>, UnpackingError> {
+    let mut address = None;
+    unpack::unpack_numbered(records, options, size_limit, |unpacker, rec| {
+        unpack_ti_txt_record(unpacker, &mut address, rec)
+    })
+}
+
+// Returns whether the record was an end record.
+fn unpack_ti_txt_record(
+    unpacker: &mut Unpacker,
+    address: &mut Option<usize>,
+    rec: Result<TiTxtRecord, TiTxtError>,
+) -> Result<bool, UnpackingError> {
+    let rec = rec?;
+    debug!("address={:X?} rec={:?}", address, rec);
+    match rec {
+        TiTxtRecord::Address(new) => *address = Some(new as usize),
+        TiTxtRecord::Data(value) => {
+            let current = address.ok_or(TiTxtError::MissingAddress)?;
+            unpacker.data(current, &value)?;
+            *address = Some(current + value.len());
+        }
+        TiTxtRecord::End => return Ok(true),
+    }
+    Ok(false)
+}
+
+pub fn load_ti_txt_file_vec<P: AsRef<Path>>(
+    path: P,
+    binary_size: usize,
+    base_offset: usize,
+) -> Result<(Vec<u8>, usize), LoadError> {
+    let options = UnpackOptions::new()
+        .base_offset(base_offset)
+        .size_limit(binary_size);
+    let unpacked = load_ti_txt_file_vec_with(path, &options)?;
+    Ok((unpacked.data, unpacked.used_bytes))
+}
+
+pub fn load_ti_txt_file_vec_with<P: AsRef<Path>>(
+    path: P,
+    options: &UnpackOptions,
+) -> Result<Unpacked<Vec<u8>>, LoadError> {
+    Ok(load_ti_txt_file_image(path, options)?.into_vec(options))
+}
+
+pub fn load_ti_txt_file_image<P: AsRef<Path>>(
+    path: P,
+    options: &UnpackOptions,
+) -> Result<Unpacked<MemoryImage>, LoadError> {
+    let path = path.as_ref();
+    crate::in_file(path, || {
+        let reader = crate::open_file(path)?;
+        crate::unpack_text(reader, TiTxtRecord::from_record_string, |records| {
+            unpack_ti_txt_records(records, options, options.size_limit)
+        })
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TiTxtWriteOptions {
+    /// The maximum number of data bytes on a single line.
+    pub line_len: u8,
+}
+
+impl Default for TiTxtWriteOptions {
+    fn default() -> Self {
+        TiTxtWriteOptions { line_len: 16 }
+    }
+}
+
+pub fn image_to_ti_txt_records(
+    image: &MemoryImage,
+    base_address: usize,
+    options: &TiTxtWriteOptions,
+) -> Result<Vec<TiTxtRecord>, WritingError> {
+    if options.line_len == 0 {
+        return Err(WritingError::InvalidRecordLength(options.line_len));
+    }
+    writer::check_end(base_address, image.end_address().unwrap_or(0), 1 << 32)?;
+
+    let mut records = Vec::new();
+    for segment in image.segments() {
+        records.push(TiTxtRecord::Address(
+            (base_address + segment.address()) as u32,
+        ));
+        for chunk in segment.data().chunks(options.line_len as usize) {
+            records.push(TiTxtRecord::Data(chunk.to_vec()));
+        }
+    }
+    records.push(TiTxtRecord::End);
+
+    Ok(records)
+}
+
+pub fn image_to_ti_txt_string(
+    image: &MemoryImage,
+    base_address: usize,
+    options: &TiTxtWriteOptions,
+) -> Result<String, WritingError> {
+    let records = image_to_ti_txt_records(image, base_address, options)?;
+    Ok(records.iter().fold(String::new(), |mut acc, record| {
+        acc.push_str(&record.to_record_string());
+        acc.push('\n');
+        acc
+    }))
+}
+
+pub fn save_ti_txt_file<P: AsRef<Path>>(
+    path: P,
+    binary: &[u8],
+    base_address: usize,
+    options: &TiTxtWriteOptions,
+) -> Result<(), SaveError> {
+    save_ti_txt_file_image(
+        path,
+        &MemoryImage::from_slice(0, binary),
+        base_address,
+        options,
+    )
+}
+
+pub fn save_ti_txt_file_image<P: AsRef<Path>>(
+    path: P,
+    image: &MemoryImage,
+    base_address: usize,
+    options: &TiTxtWriteOptions,
+) -> Result<(), SaveError> {
+    let ti_txt = image_to_ti_txt_string(image, base_address, options)?;
+    crate::write_file(path, &ti_txt)
+}
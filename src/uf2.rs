@@ -0,0 +1,185 @@
+use std::path::Path;
+
+use log::*;
+use thiserror::Error;
+
+use crate::unpack::Unpacker;
+use crate::writer;
+use crate::{
+    LoadError, MemoryImage, SaveError, UnpackOptions, Unpacked, UnpackingError, WritingError,
+};
+
+const BLOCK_LEN: usize = 512;
+const MAX_PAYLOAD_LEN: usize = 476;
+const PAYLOAD_LEN: usize = 256;
+
+const MAGIC_START0: u32 = 0x0A32_4655;
+const MAGIC_START1: u32 = 0x9E5D_5157;
+const MAGIC_END: u32 = 0x0AB1_6F30;
+
+const FLAG_NOT_MAIN_FLASH: u32 = 0x0000_0001;
+const FLAG_FAMILY_ID: u32 = 0x0000_2000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Error)]
+#[non_exhaustive]
+pub enum Uf2Error {
+    #[error("file is not a whole number of blocks")]
+    Truncated,
+    #[error("missing magic number in block {0}")]
+    MissingMagic(usize),
+    #[error("payload size ({1}) of block {0} is too large")]
+    PayloadTooLarge(usize, u32),
+    #[error("block number ({1}) of block {0} is out of sequence")]
+    BlockOutOfSequence(usize, u32),
+    #[error("only {1} of {0} blocks present")]
+    MissingBlocks(u32, u32),
+}
+
+fn read_u32(block: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(block[offset..offset + 4].try_into().unwrap())
+}
+
+/// Unpacks the blocks of a UF2 file. If `family_id` is given, blocks tagged with a different
+/// family ID are skipped, as a bootloader would.
+pub fn unpack_uf2(
+    bytes: &[u8],
+    options: &UnpackOptions,
+    family_id: Option<u32>,
+) -> Result<Unpacked<MemoryImage>, UnpackingError> {
+    if !bytes.len().is_multiple_of(BLOCK_LEN) {
+        return Err(Uf2Error::Truncated.into());
+    }
+
+    let mut unpacker = Unpacker::new(options, options.size_limit);
+    // The number and expected count of the previous block. Files may be concatenated, in which
+    // case numbering starts again from zero.
+    let mut previous: Option<(u32, u32)> = None;
+    for (n, block) in bytes.chunks_exact(BLOCK_LEN).enumerate() {
+        if read_u32(block, 0) != MAGIC_START0
+            || read_u32(block, 4) != MAGIC_START1
+            || read_u32(block, BLOCK_LEN - 4) != MAGIC_END
+        {
+            return Err(Uf2Error::MissingMagic(n).into());
+        }
+        let flags = read_u32(block, 8);
+        let address = read_u32(block, 12);
+        let payload_len = read_u32(block, 16);
+        let block_no = read_u32(block, 20);
+        let num_blocks = read_u32(block, 24);
+        let family = read_u32(block, 28);
+        debug!(
+            "block={} flags=0x{:08X} address=0x{:08X} len={} {}/{}",
+            n, flags, address, payload_len, block_no, num_blocks
+        );
+
+        match previous {
+            Some((prev_no, prev_count)) if block_no == prev_no + 1 && num_blocks == prev_count => {}
+            Some((prev_no, prev_count)) if block_no == 0 && prev_no + 1 != prev_count => {
+                return Err(Uf2Error::MissingBlocks(prev_count, prev_no + 1).into());
+            }
+            _ if block_no == 0 => {}
+            _ => return Err(Uf2Error::BlockOutOfSequence(n, block_no).into()),
+        }
+        if block_no >= num_blocks {
+            return Err(Uf2Error::BlockOutOfSequence(n, block_no).into());
+        }
+        previous = Some((block_no, num_blocks));
+
+        if payload_len as usize > MAX_PAYLOAD_LEN {
+            return Err(Uf2Error::PayloadTooLarge(n, payload_len).into());
+        }
+        let other_family = flags & FLAG_FAMILY_ID != 0 && family_id.is_some_and(|id| id != family);
+        if flags & FLAG_NOT_MAIN_FLASH != 0 || other_family {
+            continue;
+        }
+        unpacker.data(address as usize, &block[32..32 + payload_len as usize])?;
+    }
+
+    if let Some((prev_no, prev_count)) = previous {
+        if prev_no + 1 != prev_count {
+            return Err(Uf2Error::MissingBlocks(prev_count, prev_no + 1).into());
+        }
+    }
+    unpacker.finish(true)
+}
+
+pub fn load_uf2_file_image<P: AsRef<Path>>(
+    path: P,
+    options: &UnpackOptions,
+    family_id: Option<u32>,
+) -> Result<Unpacked<MemoryImage>, LoadError> {
+    let path = path.as_ref();
+    crate::in_file(path, || {
+        let bytes = crate::read_file_bytes(path)?;
+        Ok(unpack_uf2(&bytes, options, family_id)?)
+    })
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Uf2WriteOptions {
+    /// The board family ID to tag each block with, such as 0xE48BFF56 for the RP2040.
+    pub family_id: Option<u32>,
+}
+
+/// Packs the image into UF2 blocks, each holding a 256 byte aligned page. Any gaps within a
+/// page are filled with zeroes.
+pub fn image_to_uf2(
+    image: &MemoryImage,
+    base_address: usize,
+    options: &Uf2WriteOptions,
+) -> Result<Vec<u8>, WritingError> {
+    writer::check_end(base_address, image.end_address().unwrap_or(0), 1 << 32)?;
+
+    let mut pages: Vec<usize> = image
+        .segments()
+        .flat_map(|s| {
+            let first = (base_address + s.address()) / PAYLOAD_LEN;
+            let last = (base_address + s.end() - 1) / PAYLOAD_LEN;
+            first..=last
+        })
+        .collect();
+    // Segments are never adjacent, but may share a page.
+    pages.dedup();
+
+    let mut flags = 0;
+    if options.family_id.is_some() {
+        flags |= FLAG_FAMILY_ID;
+    }
+    let mut uf2 = Vec::with_capacity(pages.len() * BLOCK_LEN);
+    for (block_no, page) in pages.iter().enumerate() {
+        let address = page * PAYLOAD_LEN;
+        let mut payload = [0; PAYLOAD_LEN];
+        // The first page may start below the base address.
+        let skip = base_address.saturating_sub(address);
+        image.copy_to_slice(address + skip - base_address, &mut payload[skip..], &[0]);
+
+        let header = [
+            MAGIC_START0,
+            MAGIC_START1,
+            flags,
+            address as u32,
+            PAYLOAD_LEN as u32,
+            block_no as u32,
+            pages.len() as u32,
+            options.family_id.unwrap_or(0),
+        ];
+        for word in header {
+            uf2.extend_from_slice(&word.to_le_bytes());
+        }
+        uf2.extend_from_slice(&payload);
+        uf2.resize(uf2.len() + MAX_PAYLOAD_LEN - PAYLOAD_LEN, 0);
+        uf2.extend_from_slice(&MAGIC_END.to_le_bytes());
+    }
+
+    Ok(uf2)
+}
+
+pub fn save_uf2_file_image<P: AsRef<Path>>(
+    path: P,
+    image: &MemoryImage,
+    base_address: usize,
+    options: &Uf2WriteOptions,
+) -> Result<(), SaveError> {
+    let uf2 = image_to_uf2(image, base_address, options)?;
+    crate::write_file(path, &uf2)
+}
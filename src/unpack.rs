@@ -0,0 +1,242 @@
+use crate::records::RecordSource;
+use crate::{
+    Endian, EntryPoint, MemoryImage, OutOfWindow, OverlapPolicy, UnpackOptions, UnpackingError,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Unpacked<T> {
+    pub data: T,
+    pub used_bytes: usize,
+    pub entry_point: Option<EntryPoint>,
+    /// Data found outside of the window set by the base offset and size limit, at its original
+    /// address. Only populated when using [`OutOfWindow::Collect`].
+    pub out_of_window: MemoryImage,
+}
+
+impl<T> Unpacked<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Unpacked<U> {
+        Unpacked {
+            data: f(self.data),
+            used_bytes: self.used_bytes,
+            entry_point: self.entry_point,
+            out_of_window: self.out_of_window,
+        }
+    }
+}
+
+impl Unpacked<MemoryImage> {
+    // Flattens the image into a buffer the size of the size limit, or up to the highest address
+    // in the image if there is no limit.
+    pub(crate) fn into_vec(self, options: &UnpackOptions) -> Unpacked<Vec<u8>> {
+        self.map(|image| {
+            let size = options
+                .size_limit
+                .unwrap_or_else(|| image.end_address().unwrap_or(0));
+            let mut binary = vec![0; size];
+            image.copy_to_slice(0, &mut binary, &options.fill);
+            binary
+        })
+    }
+
+    pub(crate) fn into_array<const N: usize>(self, options: &UnpackOptions) -> Unpacked<[u8; N]> {
+        self.map(|image| {
+            let mut binary = [0; N];
+            image.copy_to_slice(0, &mut binary, &options.fill);
+            binary
+        })
+    }
+}
+
+pub(crate) fn array_size_limit<const N: usize>(options: &UnpackOptions) -> Option<usize> {
+    Some(options.size_limit.map_or(N, |limit| limit.min(N)))
+}
+
+// Format independent state used while unpacking records into a memory image.
+pub(crate) struct Unpacker<'a> {
+    options: &'a UnpackOptions,
+    size_limit: Option<usize>,
+    image: MemoryImage,
+    out_of_window: MemoryImage,
+    entry_point: Option<EntryPoint>,
+}
+
+impl<'a> Unpacker<'a> {
+    pub(crate) fn new(options: &'a UnpackOptions, size_limit: Option<usize>) -> Self {
+        Unpacker {
+            options,
+            size_limit,
+            image: MemoryImage::new(),
+            out_of_window: MemoryImage::new(),
+            entry_point: None,
+        }
+    }
+
+    // Writes data at the absolute `address` into the image, relative to the base offset. Any
+    // part of the data outside of the window set by the base offset and size limit is handled
+    // according to the out of window policy.
+    pub(crate) fn data(&mut self, address: usize, value: &[u8]) -> Result<(), UnpackingError> {
+        let base_offset = self.options.base_offset;
+        let end_addr = address
+            .checked_add(value.len())
+            .ok_or(UnpackingError::AddressOverflow {
+                address,
+                len: value.len(),
+            })?;
+        let window_end = self
+            .size_limit
+            .map_or(usize::MAX, |limit| base_offset.saturating_add(limit));
+
+        if self.options.out_of_window == OutOfWindow::Error {
+            if address < base_offset {
+                return Err(UnpackingError::AddressBelowBase {
+                    address,
+                    base_offset,
+                });
+            }
+            if end_addr > window_end {
+                return Err(UnpackingError::AddressTooHigh(
+                    end_addr - base_offset,
+                    window_end - base_offset,
+                ));
+            }
+        }
+
+        let collect = self.options.out_of_window == OutOfWindow::Collect;
+        if end_addr <= base_offset || address >= window_end {
+            if collect {
+                self.out_of_window.write(address, value);
+            }
+            return Ok(());
+        }
+
+        let start = address.clamp(base_offset, window_end);
+        let end = end_addr.clamp(base_offset, window_end);
+        if collect {
+            self.out_of_window.write(address, &value[..start - address]);
+            self.out_of_window.write(end, &value[end - address..]);
+        }
+        if start == end {
+            return Ok(());
+        }
+        write_data(
+            &mut self.image,
+            start - base_offset,
+            &value[start - address..end - address],
+            self.options.overlap,
+        )
+    }
+
+    // Like `data`, but for data at a word address when using word addressing.
+    pub(crate) fn word_data(&mut self, address: usize, value: &[u8]) -> Result<(), UnpackingError> {
+        let word_len = self.options.address_unit.bytes();
+        if word_len == 1 {
+            return self.data(address, value);
+        }
+        if !value.len().is_multiple_of(word_len) {
+            return Err(UnpackingError::PartialWord(value.len()));
+        }
+
+        let address = address
+            .checked_mul(word_len)
+            .ok_or(UnpackingError::AddressOverflow {
+                address,
+                len: value.len(),
+            })?;
+        if self.options.word_endian == Endian::Little {
+            return self.data(address, value);
+        }
+        let swapped: Vec<u8> = value
+            .chunks_exact(word_len)
+            .flat_map(|word| word.iter().rev())
+            .copied()
+            .collect();
+        self.data(address, &swapped)
+    }
+
+    pub(crate) fn entry_point(&mut self, new: EntryPoint) -> Result<(), UnpackingError> {
+        match self.entry_point {
+            Some(old) if old != new => Err(UnpackingError::ConflictingEntryPoint(old, new)),
+            _ => {
+                self.entry_point = Some(new);
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) fn finish(self, seen_eof: bool) -> Result<Unpacked<MemoryImage>, UnpackingError> {
+        if self.options.strict_eof && !seen_eof {
+            return Err(UnpackingError::MissingEof);
+        }
+
+        Ok(Unpacked {
+            used_bytes: self.image.len(),
+            data: self.image,
+            entry_point: self.entry_point,
+            out_of_window: self.out_of_window,
+        })
+    }
+}
+
+// Feeds each record to `unpack_record`, which returns whether it was the format's end record,
+// adding line context to any error. Records after the end record are only looked at when
+// `strict_eof` is set, to report them as an error.
+pub(crate) fn unpack_numbered<T>(
+    mut records: impl RecordSource<T>,
+    options: &UnpackOptions,
+    size_limit: Option<usize>,
+    mut unpack_record: impl FnMut(&mut Unpacker, T) -> Result<bool, UnpackingError>,
+) -> Result<Unpacked<MemoryImage>, UnpackingError> {
+    let mut unpacker = Unpacker::new(options, size_limit);
+    let mut seen_eof = false;
+
+    while let Some(rec) = records.next() {
+        let is_eof = unpack_record(&mut unpacker, rec.record)
+            .map_err(|err| err.at_line(rec.line, records.text()))?;
+        if is_eof {
+            seen_eof = true;
+            break;
+        }
+    }
+
+    if seen_eof && options.strict_eof {
+        if let Some(rec) = records.next() {
+            return Err(UnpackingError::DataAfterEof { line: rec.line });
+        }
+    }
+
+    unpacker.finish(seen_eof)
+}
+
+fn write_data(
+    image: &mut MemoryImage,
+    address: usize,
+    value: &[u8],
+    overlap: OverlapPolicy,
+) -> Result<(), UnpackingError> {
+    if overlap == OverlapPolicy::LastWins || !image.overlaps(address, value.len()) {
+        image.write(address, value);
+        return Ok(());
+    }
+
+    let overlap_err = UnpackingError::Overlap {
+        address,
+        len: value.len(),
+    };
+    match overlap {
+        OverlapPolicy::Error => return Err(overlap_err),
+        OverlapPolicy::ErrorIfDifferent => {
+            let differs = value
+                .iter()
+                .enumerate()
+                .any(|(n, b)| image.get(address + n).is_some_and(|old| old != *b));
+            if differs {
+                return Err(overlap_err);
+            }
+            image.write(address, value);
+        }
+        OverlapPolicy::FirstWins => image.write_unset(address, value),
+        OverlapPolicy::LastWins => unreachable!(),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,149 @@
+use ihex::{Record, WriterError};
+use thiserror::Error;
+
+use crate::{EntryPoint, MemoryImage};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddressMode {
+    /// Use Extended Linear Address records, covering a 32-bit address space (I32HEX).
+    Linear,
+    /// Use Extended Segment Address records, covering a 20-bit address space (I16HEX).
+    Segment,
+}
+
+impl AddressMode {
+    fn address_limit(self) -> u64 {
+        match self {
+            AddressMode::Linear => 1 << 32,
+            AddressMode::Segment => 1 << 20,
+        }
+    }
+
+    fn extended_address(self, address: usize) -> Record {
+        let upper = (address >> 16) as u16;
+        match self {
+            AddressMode::Linear => Record::ExtendedLinearAddress(upper),
+            AddressMode::Segment => Record::ExtendedSegmentAddress(upper << 12),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// The maximum number of data bytes in a single data record.
+    pub record_len: u8,
+    pub address_mode: AddressMode,
+    pub entry_point: Option<EntryPoint>,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            record_len: 16,
+            address_mode: AddressMode::Linear,
+            entry_point: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Error)]
+#[non_exhaustive]
+pub enum WritingError {
+    #[error("Record length ({0}) not supported by the output format")]
+    InvalidRecordLength(u8),
+    #[error("Address ({0}) greater than addressable limit ({1})")]
+    AddressTooHigh(usize, usize),
+    #[error("Data at address ({0:#X}) lies outside of the export window")]
+    OutsideWindow(usize),
+    #[error("Export window start ({0:#X}) is after its end ({1:#X})")]
+    InvalidWindow(usize, usize),
+    #[error("Memory depth must be at least one word")]
+    ZeroDepth,
+    #[error("Header length ({0}) longer than a single record can hold ({1})")]
+    HeaderTooLong(usize, usize),
+    #[error("Image holds no data")]
+    EmptyImage,
+    #[error("Base address ({0:#X}) moves data past the end of the address space")]
+    BaseAddressOverflow(usize),
+    #[error("Name `{0}` is not a valid identifier")]
+    InvalidName(String),
+    #[error("Error while writing IHEX records: {0}")]
+    Writer(WriterError),
+}
+
+impl From<WriterError> for WritingError {
+    fn from(err: WriterError) -> Self {
+        WritingError::Writer(err)
+    }
+}
+
+// Checks that data ending at `end` once moved to `base_address` lies below `limit`. Limits are
+// `u64` as a 32-bit address space doesn't fit in a `usize` on 32-bit targets.
+pub(crate) fn check_end(base_address: usize, end: usize, limit: u64) -> Result<(), WritingError> {
+    let error_limit = usize::try_from(limit).unwrap_or(usize::MAX);
+    let end = base_address
+        .checked_add(end)
+        .ok_or(WritingError::AddressTooHigh(usize::MAX, error_limit))?;
+    if end as u64 > limit {
+        return Err(WritingError::AddressTooHigh(end, error_limit));
+    }
+    Ok(())
+}
+
+pub fn image_to_records(
+    image: &MemoryImage,
+    base_address: usize,
+    options: &WriteOptions,
+) -> Result<Vec<Record>, WritingError> {
+    if options.record_len == 0 {
+        return Err(WritingError::InvalidRecordLength(options.record_len));
+    }
+
+    let limit = options.address_mode.address_limit();
+    let mut records = Vec::new();
+    let mut upper_address = 0;
+
+    for segment in image.segments() {
+        check_end(base_address, segment.end(), limit)?;
+
+        let mut address = base_address + segment.address();
+        for chunk in segment.data().chunks(options.record_len as usize) {
+            // Data records can't cross a 64KiB boundary, so split the chunk if it would.
+            let mut chunk = chunk;
+            while !chunk.is_empty() {
+                if address >> 16 != upper_address {
+                    upper_address = address >> 16;
+                    records.push(options.address_mode.extended_address(address));
+                }
+
+                let len = chunk.len().min(0x1_0000 - (address & 0xFFFF));
+                records.push(Record::Data {
+                    offset: address as u16,
+                    value: chunk[..len].to_vec(),
+                });
+                address += len;
+                chunk = &chunk[len..];
+            }
+        }
+    }
+
+    match options.entry_point {
+        Some(EntryPoint::Linear(address)) => records.push(Record::StartLinearAddress(address)),
+        Some(EntryPoint::Segment { cs, ip }) => {
+            records.push(Record::StartSegmentAddress { cs, ip })
+        }
+        None => {}
+    }
+    records.push(Record::EndOfFile);
+
+    Ok(records)
+}
+
+pub fn image_to_string(
+    image: &MemoryImage,
+    base_address: usize,
+    options: &WriteOptions,
+) -> Result<String, WritingError> {
+    let records = image_to_records(image, base_address, options)?;
+    Ok(ihex::create_object_file_representation(&records)?)
+}
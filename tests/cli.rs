@@ -0,0 +1,93 @@
+#![cfg(feature = "cli")]
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn ihex(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ihex"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("ihex_ext_cli_{}_{}", std::process::id(), name));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn info() {
+    let path = temp_file(
+        "info.hex",
+        ":0400000001020304F2\n:0400000500000001F6\n:00000001FF\n",
+    );
+    let output = ihex(&["info", path.to_str().unwrap()]);
+    fs::remove_file(&path).unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("format:      IntelHex"));
+    assert!(stdout.contains("data bytes:  4"));
+    assert!(stdout.contains("entry point: 0x00000001"));
+}
+
+#[test]
+fn convert() {
+    let ihex_text = ":0400000001020304F2\n:00000001FF\n";
+    let input = temp_file("convert.hex", ihex_text);
+    let srec = input.with_extension("srec");
+    let back = input.with_extension("out.hex");
+
+    let to_srec = ihex(&["convert", input.to_str().unwrap(), srec.to_str().unwrap()]);
+    let to_ihex = ihex(&["convert", srec.to_str().unwrap(), back.to_str().unwrap()]);
+    let converted = fs::read_to_string(&back);
+    for path in [&input, &srec, &back] {
+        let _ = fs::remove_file(path);
+    }
+
+    assert!(to_srec.status.success());
+    assert!(to_ihex.status.success());
+    assert_eq!(converted.unwrap(), ihex_text);
+}
+
+#[test]
+fn diff() {
+    let a = temp_file("diff_a.hex", ":0400000001020304F2\n:00000001FF\n");
+    let b = temp_file("diff_b.hex", ":0400000001020305F1\n:00000001FF\n");
+    let same = ihex(&["diff", a.to_str().unwrap(), a.to_str().unwrap()]);
+    let different = ihex(&["diff", a.to_str().unwrap(), b.to_str().unwrap()]);
+    fs::remove_file(&a).unwrap();
+    fs::remove_file(&b).unwrap();
+
+    assert_eq!(same.status.code(), Some(0));
+    assert_eq!(different.status.code(), Some(1));
+    let stdout = String::from_utf8(different.stdout).unwrap();
+    assert!(stdout.contains("  - 04"));
+    assert!(stdout.contains("  + 05"));
+}
+
+#[test]
+fn merge() {
+    let a = temp_file("merge_a.hex", ":0400000500000001F6\n:00000001FF\n");
+    let b = temp_file("merge_b.hex", ":0400000500000011E6\n:00000001FF\n");
+    let output = a.with_extension("out.hex");
+    let no_inputs = ihex(&["merge", output.to_str().unwrap()]);
+    let conflicting = ihex(&[
+        "merge",
+        output.to_str().unwrap(),
+        a.to_str().unwrap(),
+        b.to_str().unwrap(),
+    ]);
+    fs::remove_file(&a).unwrap();
+    fs::remove_file(&b).unwrap();
+
+    assert_eq!(no_inputs.status.code(), Some(2));
+    assert_eq!(conflicting.status.code(), Some(2));
+    assert!(String::from_utf8(conflicting.stderr)
+        .unwrap()
+        .contains("entry point"));
+    assert!(!output.exists());
+}
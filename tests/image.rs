@@ -0,0 +1,257 @@
+use std::ops::Range;
+
+use ihex::Reader;
+use ihex_ext::srec::{self, SrecReaderExt};
+use ihex_ext::*;
+
+#[test]
+fn merge_inputs() {
+    let bootloader = ":0400000001020304F2\n:0400000500000001F6\n:00000001FF\n";
+    let app = ":020002000304F5\n:020010000506E3\n:0400000500000011E6\n:00000001FF\n";
+
+    let unpacked = merge(
+        [
+            MergeInput::new(Reader::new(bootloader)),
+            MergeInput::new(Reader::new(app))
+                .relocate(0x100)
+                .keep_entry_point(false),
+        ],
+        &UnpackOptions::new(),
+    )
+    .unwrap();
+    let segments: Vec<_> = unpacked
+        .data
+        .segments()
+        .map(|s| (s.address(), s.data().to_vec()))
+        .collect();
+    assert_eq!(
+        segments,
+        vec![
+            (0x000, vec![0x01, 0x02, 0x03, 0x04]),
+            (0x102, vec![0x03, 0x04]),
+            (0x110, vec![0x05, 0x06])
+        ]
+    );
+    assert_eq!(unpacked.entry_point, Some(EntryPoint::Linear(1)));
+    assert_eq!(unpacked.used_bytes, 8);
+
+    // The app agrees with the bootloader where they overlap, but the patch does not.
+    let patch = ":020003000506F0\n:00000001FF\n";
+    let conflicting = merge(
+        [
+            MergeInput::new(Reader::new(bootloader)),
+            MergeInput::new(Reader::new(app)).keep_entry_point(false),
+            MergeInput::new(Reader::new(patch)),
+        ],
+        &UnpackOptions::new(),
+    );
+    assert_eq!(
+        conflicting,
+        Err(MergeError::Conflict {
+            input: 2,
+            address: 0x03
+        })
+    );
+
+    // Already unpacked images can be merged alongside records.
+    let srec = "S1050200AABB93\nS9030000FC\n";
+    let unpacked_srec = srec::SrecReader::new(srec)
+        .unpack(&UnpackOptions::new())
+        .unwrap();
+    let entry_points = merge(
+        [
+            MergeInput::new(Reader::new(bootloader)),
+            MergeInput::unpacked(unpacked_srec),
+            MergeInput::new(Reader::new(app)),
+        ],
+        &UnpackOptions::new(),
+    );
+    assert_eq!(
+        entry_points,
+        Err(MergeError::ConflictingEntryPoint {
+            input: 2,
+            old: EntryPoint::Linear(1),
+            new: EntryPoint::Linear(0x11)
+        })
+    );
+}
+
+#[test]
+fn diff_images() {
+    let mut old = MemoryImage::from_slice(0x100, &[1, 2, 3, 4, 5, 6]);
+    old.write(0x200, &[0xAA; 4]);
+    let mut new = MemoryImage::from_slice(0x102, &[3, 0, 0, 6, 7, 8]);
+    new.write(0x300, &[0xBB; 2]);
+
+    let diff = diff(&old, &new);
+    let changes: Vec<_> = diff
+        .changes
+        .iter()
+        .map(|c| (c.kind, c.range.clone()))
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            (ChangeKind::Removed, 0x100..0x102),
+            (ChangeKind::Changed, 0x103..0x105),
+            (ChangeKind::Added, 0x106..0x108),
+            (ChangeKind::Removed, 0x200..0x204),
+            (ChangeKind::Added, 0x300..0x302),
+        ]
+    );
+    assert_eq!(
+        diff.to_string().lines().nth(1),
+        Some("changed 0x00000103..0x00000105 (2 bytes)")
+    );
+    assert!(ihex_ext::diff(&old, &old).is_empty());
+}
+
+#[test]
+fn checksums() {
+    let check = b"123456789";
+    assert_eq!(CrcParams::CRC32.compute(check), 0xCBF4_3926);
+    assert_eq!(CrcParams::CRC32_MPEG2.compute(check), 0x0376_E6E7);
+    assert_eq!(CrcParams::CRC16_CCITT.compute(check), 0x29B1);
+    assert_eq!(CrcParams::CRC16_XMODEM.compute(check), 0x31C3);
+    assert_eq!(CrcParams::CRC16_KERMIT.compute(check), 0x2189);
+    let crc5_usb = CrcParams::new(5, 0x05)
+        .unwrap()
+        .init(0x1F)
+        .reflect(true, true)
+        .xor_out(0x1F);
+    assert_eq!(crc5_usb.compute(check), 0x19);
+    assert_eq!(
+        CrcParams::new(64, 0x1B),
+        Err(CrcError::UnsupportedWidth(64))
+    );
+
+    let sha = Checksum::Sha256.compute(b"abc");
+    let Digest::Sha256(sha) = sha else {
+        panic!("unexpected digest {:?}", sha);
+    };
+    assert_eq!(sha[..4], [0xBA, 0x78, 0x16, 0xBF]);
+    assert_eq!(sha[28..], [0xF2, 0x00, 0x15, 0xAD]);
+
+    // "1234" and "6789" with a gap where "5" would be, starting one byte before the range.
+    let mut image = MemoryImage::from_slice(0xFF, b"01234");
+    image.write(0x105, b"6789");
+    let crc32 = Checksum::Crc(CrcParams::CRC32);
+    assert_eq!(
+        image.checksum(0x100..0x109, crc32, GapPolicy::Fill(b'5')),
+        Digest::Crc(0xCBF4_3926)
+    );
+    assert_eq!(
+        image.checksum(0x100..0x10C, crc32, GapPolicy::Skip),
+        crc32.compute(b"12346789")
+    );
+    let reversed = Range {
+        start: 0x105,
+        end: 0x100,
+    };
+    assert_eq!(
+        image.checksum(reversed, crc32, GapPolicy::Fill(b'5')),
+        crc32.compute(&[])
+    );
+}
+
+#[test]
+fn patch_image() {
+    let mut image = Reader::new(":0400000001020304F2\n:00000001FF\n")
+        .unpack(&UnpackOptions::new())
+        .unwrap()
+        .data;
+
+    let mut patcher = image.patcher();
+    patcher.u16(0x04, 0x1234, Endian::Big).unwrap();
+    patcher.u32(0x06, 0x0000_0042, Endian::Little).unwrap();
+    assert_eq!(
+        patcher.u16(0x03, 0xFFFF, Endian::Little),
+        Err(PatchError::Overlap { address: 3, len: 2 })
+    );
+    let crc = patcher
+        .crc(
+            0x0C,
+            0x00..0x0C,
+            CrcParams::CRC16_CCITT,
+            GapPolicy::Fill(0xFF),
+            Endian::Little,
+        )
+        .unwrap();
+    assert_eq!(
+        patcher.crc(
+            0x0A,
+            0x00..0x0C,
+            CrcParams::CRC32,
+            GapPolicy::Skip,
+            Endian::Little
+        ),
+        Err(PatchError::CrcInRange { address: 0x0A })
+    );
+
+    let expected = [1, 2, 3, 4, 0x12, 0x34, 0x42, 0, 0, 0, 0xFF, 0xFF];
+    assert_eq!(crc, CrcParams::CRC16_CCITT.compute(&expected));
+    let mut patched = expected.to_vec();
+    patched.extend_from_slice(&(crc as u16).to_le_bytes());
+    assert_eq!(image.to_vec(), (patched.clone(), 12));
+
+    image
+        .patcher()
+        .allow_overwrite(true)
+        .bytes(0x00, &[0xAA])
+        .unwrap();
+    patched[0] = 0xAA;
+    let ihex = image_to_string(&image, 0, &WriteOptions::default()).unwrap();
+    let reloaded = Reader::new(&ihex).unpack(&UnpackOptions::new()).unwrap();
+    assert_eq!(reloaded.data.to_vec(), (patched, 12));
+}
+
+#[test]
+fn write_merges_segments() {
+    fn segments(image: &MemoryImage) -> Vec<(usize, Vec<u8>)> {
+        image
+            .segments()
+            .map(|s| (s.address(), s.data().to_vec()))
+            .collect()
+    }
+
+    let mut image = MemoryImage::from_slice(0x10, &[1, 2, 3, 4]);
+    image.write(0x20, &[5, 6]);
+
+    // Within an existing segment.
+    image.write(0x11, &[0xAA, 0xBB]);
+    assert_eq!(
+        segments(&image),
+        vec![(0x10, vec![1, 0xAA, 0xBB, 4]), (0x20, vec![5, 6])]
+    );
+
+    // Touching a neighbour on either side.
+    image.write(0x0E, &[7, 8]);
+    image.write(0x22, &[9]);
+    assert_eq!(
+        segments(&image),
+        vec![(0x0E, vec![7, 8, 1, 0xAA, 0xBB, 4]), (0x20, vec![5, 6, 9])]
+    );
+
+    // Bridging two segments, overlapping both.
+    image.write(0x13, &[0xCC; 14]);
+    let mut bridged = vec![7, 8, 1, 0xAA, 0xBB];
+    bridged.extend_from_slice(&[0xCC; 14]);
+    bridged.extend_from_slice(&[6, 9]);
+    assert_eq!(segments(&image), vec![(0x0E, bridged)]);
+
+    image.write(0x30, &[0xDD]);
+    assert_eq!(image.get(0x0D), None);
+    assert_eq!(image.get(0x0E), Some(7));
+    assert_eq!(image.get(0x22), Some(9));
+    assert_eq!(image.get(0x23), None);
+    assert_eq!(image.get(0x2F), None);
+    assert_eq!(image.get(0x30), Some(0xDD));
+    assert_eq!(image.get(0x31), None);
+
+    // Data past the end of the address space is dropped rather than overflowing.
+    assert!(MemoryImage::from_slice(usize::MAX, &[1]).is_empty());
+    assert_eq!(
+        MemoryImage::from_slice(usize::MAX - 1, &[1, 2]).end_address(),
+        Some(usize::MAX)
+    );
+}
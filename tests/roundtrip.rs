@@ -0,0 +1,386 @@
+use std::env;
+use std::fs;
+use std::ops::Range;
+
+use ihex::Reader;
+use ihex_ext::*;
+
+#[test]
+fn save_and_load_file() {
+    let path = env::temp_dir().join(format!("ihex_ext_roundtrip_{}.hex", std::process::id()));
+    let binary: Vec<u8> = (0..=255).cycle().take(0x1_0123).collect();
+
+    save_file(&path, &binary, 0x0800_0000, &WriteOptions::default()).unwrap();
+    let loaded = load_file_vec(&path, binary.len(), 0x0800_0000);
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.unwrap(), (binary.clone(), binary.len()));
+}
+
+#[test]
+fn image_roundtrip() {
+    let mut image = MemoryImage::new();
+    image.write(0x0000_FFF0, &[0xAA; 0x20]);
+    image.write(0x0002_0000, &[0x55; 3]);
+
+    for address_mode in [AddressMode::Linear, AddressMode::Segment] {
+        let options = WriteOptions {
+            record_len: 7,
+            address_mode,
+            entry_point: Some(EntryPoint::Linear(0x1234)),
+        };
+        let ihex = image_to_string(&image, 0, &options).unwrap();
+        assert_eq!(Reader::new(&ihex).to_image(0).unwrap(), image);
+    }
+}
+
+#[test]
+fn address_out_of_range() {
+    let options = WriteOptions {
+        address_mode: AddressMode::Segment,
+        ..WriteOptions::default()
+    };
+    assert_eq!(
+        image_to_string(&MemoryImage::from_slice(0xF_FFFF, &[0, 0]), 0, &options),
+        Err(WritingError::AddressTooHigh(0x10_0001, 0x10_0000))
+    );
+    assert_eq!(
+        image_to_string(&MemoryImage::from_slice(0x10, &[0]), usize::MAX, &options),
+        Err(WritingError::AddressTooHigh(usize::MAX, 0x10_0000))
+    );
+}
+
+#[test]
+fn srec_roundtrip() {
+    use ihex_ext::srec::*;
+
+    let mut image = MemoryImage::new();
+    image.write(0x0001_FFF0, &[0xAA; 0x40]);
+    image.write(0x0010_0000, &[0x55; 3]);
+
+    let options = SrecWriteOptions {
+        header: b"test".to_vec(),
+        entry_point: Some(0x0002_0000),
+        ..SrecWriteOptions::default()
+    };
+    let srec = image_to_srec_string(&image, 0, &options).unwrap();
+    assert!(srec.starts_with("S00700007465737438\n"));
+    assert!(srec.ends_with("S5030003F9\nS804020000F9\n"));
+
+    let unpacked = SrecReader::new(&srec)
+        .unpack(&UnpackOptions::new().strict_eof(true))
+        .unwrap();
+    assert_eq!(unpacked.data, image);
+    assert_eq!(unpacked.entry_point, Some(EntryPoint::Linear(0x0002_0000)));
+
+    assert_eq!(
+        image_to_srec_string(&image, usize::MAX, &options),
+        Err(WritingError::AddressTooHigh(usize::MAX, 1 << 32))
+    );
+
+    let long_header = SrecWriteOptions {
+        header: vec![b'x'; 253],
+        ..options
+    };
+    assert_eq!(
+        image_to_srec_string(&image, 0, &long_header),
+        Err(WritingError::HeaderTooLong(253, 252))
+    );
+}
+
+#[test]
+fn srec_without_entry_point() {
+    use ihex_ext::srec::*;
+
+    let ihex = ":0400000001020304F2\n:00000001FF\n";
+    let unpacked = Reader::new(ihex).unpack(&UnpackOptions::new()).unwrap();
+    let srec = image_to_srec_string(&unpacked.data, 0, &SrecWriteOptions::default()).unwrap();
+    assert!(srec.ends_with("S9030000FC\n"));
+
+    let unpacked = SrecReader::new(&srec)
+        .unpack(&UnpackOptions::new())
+        .unwrap();
+    assert_eq!(unpacked.entry_point, None);
+    let written = image_to_string(&unpacked.data, 0, &WriteOptions::default()).unwrap();
+    assert_eq!(written, ihex);
+
+    let unpacked = SrecReader::new(&srec)
+        .unpack(&UnpackOptions::new().zero_entry_point(true))
+        .unwrap();
+    assert_eq!(unpacked.entry_point, Some(EntryPoint::Linear(0)));
+}
+
+#[test]
+fn srec_count_mismatch() {
+    use ihex_ext::srec::*;
+
+    let srec = "S1050000AABB95\nS5030002FA\nS9030000FC\n";
+    assert_eq!(
+        SrecReader::new(srec).to_vec_minimal(0),
+        Err(UnpackingError::SrecParsing(SrecError::CountMismatch(2, 1)))
+    );
+}
+
+#[test]
+fn ti_txt_roundtrip() {
+    use ihex_ext::ti_txt::*;
+
+    let ti_txt = "@F000\n31 40 00 03\n3F 40\n@FFFE\n00 F0\nq\n";
+    let unpacked = TiTxtReader::new(ti_txt)
+        .unpack(&UnpackOptions::new())
+        .unwrap();
+    let mut expected = MemoryImage::from_slice(0xF000, &[0x31, 0x40, 0x00, 0x03, 0x3F, 0x40]);
+    expected.write(0xFFFE, &[0x00, 0xF0]);
+    assert_eq!(unpacked.data, expected);
+
+    let options = TiTxtWriteOptions { line_len: 4 };
+    let written = image_to_ti_txt_string(&unpacked.data, 0, &options).unwrap();
+    assert_eq!(written, "@F000\n31 40 00 03\n3F 40\n@FFFE\n00 F0\nq\n");
+    assert_eq!(
+        image_to_ti_txt_string(&unpacked.data, usize::MAX, &options),
+        Err(WritingError::AddressTooHigh(usize::MAX, 1 << 32))
+    );
+
+    assert_eq!(
+        TiTxtReader::new("31 40\nq\n").unpack(&UnpackOptions::new()),
+        Err(UnpackingError::TiTxtParsing(TiTxtError::MissingAddress))
+    );
+
+    let path = env::temp_dir().join(format!("ihex_ext_ti_{}.txt", std::process::id()));
+    fs::write(&path, ti_txt).unwrap();
+    let loaded = load_file_auto(&path, &UnpackOptions::new().base_offset(0xF000));
+    fs::remove_file(&path).unwrap();
+    let (format, loaded) = loaded.unwrap();
+    assert_eq!(format, FileFormat::TiTxt);
+    assert_eq!(loaded.data.start_address(), Some(0));
+}
+
+#[test]
+fn uf2_roundtrip() {
+    use ihex_ext::uf2::*;
+
+    let mut image = MemoryImage::from_slice(0x1000_00F0, &[0xAA; 0x20]);
+    image.write(0x1000_0400, &[0x55; 4]);
+    let options = Uf2WriteOptions {
+        family_id: Some(0xE48B_FF56),
+    };
+    let uf2 = image_to_uf2(&image, 0, &options).unwrap();
+    assert_eq!(uf2.len(), 3 * 512);
+    assert_eq!(detect_format(&uf2), FileFormat::Uf2);
+    assert_eq!(
+        image_to_uf2(&image, usize::MAX, &options),
+        Err(WritingError::AddressTooHigh(usize::MAX, 1 << 32))
+    );
+
+    // Gaps within a page are filled with zeroes.
+    let unpacked = unpack_uf2(&uf2, &UnpackOptions::new(), Some(0xE48B_FF56)).unwrap();
+    let segments: Vec<_> = unpacked.data.segments().map(|s| s.range()).collect();
+    assert_eq!(
+        segments,
+        vec![0x1000_0000..0x1000_0200, 0x1000_0400..0x1000_0500]
+    );
+    assert_eq!(unpacked.data.get(0x1000_00F0), Some(0xAA));
+    assert_eq!(unpacked.data.get(0x1000_0110), Some(0x00));
+
+    let other_family = unpack_uf2(&uf2, &UnpackOptions::new(), Some(0x68ED_2B88)).unwrap();
+    assert!(other_family.data.is_empty());
+
+    assert_eq!(
+        unpack_uf2(&uf2[..1024], &UnpackOptions::new(), None),
+        Err(UnpackingError::Uf2Parsing(Uf2Error::MissingBlocks(3, 2)))
+    );
+    assert_eq!(
+        unpack_uf2(&uf2[512..], &UnpackOptions::new(), None),
+        Err(UnpackingError::Uf2Parsing(Uf2Error::BlockOutOfSequence(
+            0, 1
+        )))
+    );
+}
+
+#[test]
+fn bin_export_window() {
+    let mut image = MemoryImage::from_slice(0x0FFE, &[1, 2, 3, 4]);
+    image.write(0x1008, &[5, 6]);
+
+    let export = |out_of_range| {
+        let options = BinExportOptions {
+            fill: 0x00,
+            out_of_range,
+        };
+        let mut binary = Vec::new();
+        write_bin(&image, 0x1000..0x100A, &options, &mut binary).map(|_| binary)
+    };
+    assert!(matches!(
+        export(OutOfRange::Error),
+        Err(SaveError::Writing(WritingError::OutsideWindow(0x0FFE)))
+    ));
+    assert_eq!(
+        export(OutOfRange::Clip).unwrap(),
+        [3, 4, 0, 0, 0, 0, 0, 0, 5, 6]
+    );
+    assert_eq!(
+        export(OutOfRange::Ignore).unwrap(),
+        [3, 4, 0, 0, 0, 0, 0, 0, 5, 6]
+    );
+
+    // Bytes inside the window are kept even when their segment crosses its edge.
+    let crossing = MemoryImage::from_slice(0x00, &[0xAA; 0x20]);
+    let options = BinExportOptions {
+        fill: 0x00,
+        out_of_range: OutOfRange::Ignore,
+    };
+    let mut binary = Vec::new();
+    write_bin(&crossing, 0x10..0x20, &options, &mut binary).unwrap();
+    assert_eq!(binary, [0xAA; 0x10]);
+
+    let mut binary = Vec::new();
+    let options = BinExportOptions {
+        out_of_range: OutOfRange::Clip,
+        ..BinExportOptions::default()
+    };
+    let reversed = Range {
+        start: 0x20,
+        end: 0x8,
+    };
+    assert!(matches!(
+        write_bin(&image, reversed, &options, &mut binary),
+        Err(SaveError::Writing(WritingError::InvalidWindow(0x20, 0x8)))
+    ));
+
+    let path = env::temp_dir().join(format!("ihex_ext_bin_{}.bin", std::process::id()));
+    let options = BinExportOptions::default();
+    save_bin_file(&path, &image, 0x0FFE..0x100A, &options).unwrap();
+    let loaded = load_bin_file(&path, 0x0FFE);
+    let overflow = load_bin_file(&path, usize::MAX - 4);
+    fs::remove_file(&path).unwrap();
+    assert!(matches!(
+        overflow.unwrap_err().kind(),
+        LoadError::Unpacking(UnpackingError::AddressOverflow { len: 12, .. })
+    ));
+    let loaded = loaded.unwrap();
+    assert_eq!(loaded.start_address(), Some(0x0FFE));
+    assert_eq!(loaded.get(0x1002), Some(0xFF));
+    assert_eq!(loaded.get(0x1009), Some(6));
+}
+
+#[test]
+fn mem_init_formats() {
+    use ihex_ext::mem_init::*;
+
+    let mut image = MemoryImage::from_slice(0x0, &[0x01, 0x02, 0x03, 0x04, 0x05]);
+    image.write(0x10, &[0xAA, 0xBB]);
+    let options = MemInitOptions {
+        word_width: WordWidth::Bits16,
+        endian: Endian::Big,
+        depth: Some(12),
+        fill: 0xFF,
+        address_directives: true,
+    };
+
+    let readmemh = image_to_mem_init(&image, MemInitFormat::ReadMemH, &options).unwrap();
+    assert_eq!(readmemh, "@0\n0102\n0304\n05FF\n@8\nAABB\n");
+
+    let options = MemInitOptions {
+        endian: Endian::Little,
+        ..options
+    };
+    let mif = image_to_mem_init(&image, MemInitFormat::Mif, &options).unwrap();
+    assert_eq!(
+        mif,
+        "WIDTH=16;\nDEPTH=12;\n\nADDRESS_RADIX=HEX;\nDATA_RADIX=HEX;\n\nCONTENT BEGIN\n\
+         \t0 : 0201;\n\t1 : 0403;\n\t2 : FF05;\n\t[3..7] : FFFF;\n\t8 : BBAA;\n\
+         \t[9..B] : FFFF;\nEND;\n"
+    );
+
+    let options = MemInitOptions {
+        word_width: WordWidth::Bits32,
+        depth: None,
+        ..options
+    };
+    let coe = image_to_mem_init(&image, MemInitFormat::Coe, &options).unwrap();
+    assert_eq!(
+        coe,
+        "memory_initialization_radix=16;\nmemory_initialization_vector=\n\
+         04030201,\nFFFFFF05,\nFFFFFFFF,\nFFFFFFFF,\nFFFFBBAA;\n"
+    );
+
+    let options = MemInitOptions {
+        depth: Some(4),
+        ..options
+    };
+    assert_eq!(
+        image_to_mem_init(&image, MemInitFormat::Coe, &options),
+        Err(WritingError::AddressTooHigh(0x12, 0x10))
+    );
+    assert_eq!(
+        image_to_mem_init(
+            &MemoryImage::new(),
+            MemInitFormat::Mif,
+            &MemInitOptions::default()
+        ),
+        Err(WritingError::ZeroDepth)
+    );
+    let options = MemInitOptions {
+        depth: Some(usize::MAX),
+        ..MemInitOptions::default()
+    };
+    assert_eq!(
+        image_to_mem_init(&image, MemInitFormat::Mif, &options),
+        Err(WritingError::AddressTooHigh(0x12, usize::MAX))
+    );
+}
+
+#[test]
+fn codegen_sources() {
+    use ihex_ext::codegen::*;
+
+    let mut image = MemoryImage::from_slice(0x0, &[0x01, 0x02, 0x03]);
+    image.write(0x5, &[0x04]);
+    let options = CodegenOptions {
+        bytes_per_line: 4,
+        ..CodegenOptions::default()
+    };
+
+    assert_eq!(
+        image_to_rust(&image, 0x0800_0000, &options).unwrap(),
+        "// Generated by ihex_ext. Do not edit.\n\n\
+         pub const FW_ADDRESS: usize = 0x08000000;\n\
+         pub static FW: [u8; 6] = [\n    \
+         0x01, 0x02, 0x03, 0xFF,\n    \
+         0xFF, 0x04,\n\
+         ];\n\n"
+    );
+
+    let options = CodegenOptions {
+        name: "Boot".to_owned(),
+        per_segment: true,
+        ..options
+    };
+    let c = image_to_c(&image, 0x0800_0000, &options).unwrap();
+    assert!(c.contains("#define BOOT_SEGMENT_COUNT 2u\n"));
+    assert!(c.contains(
+        "#define BOOT_1_ADDRESS 0x08000005u\n\
+         #define BOOT_1_SIZE 1u\n\
+         static const uint8_t boot_1[1] = {\n    0x04,\n};\n"
+    ));
+    assert!(c.ends_with("#endif\n"));
+    let rust = image_to_rust(&image, 0x0800_0000, &options).unwrap();
+    assert!(rust.contains("pub const BOOT_SEGMENT_COUNT: usize = 2;\n"));
+
+    assert_eq!(
+        image_to_c(&MemoryImage::new(), 0, &options),
+        Err(WritingError::EmptyImage)
+    );
+    assert_eq!(
+        image_to_c(&image, usize::MAX, &options),
+        Err(WritingError::BaseAddressOverflow(usize::MAX))
+    );
+    let options = CodegenOptions {
+        name: "boot-loader".to_owned(),
+        ..options
+    };
+    assert_eq!(
+        image_to_rust(&image, 0, &options),
+        Err(WritingError::InvalidName("boot-loader".to_owned()))
+    );
+}
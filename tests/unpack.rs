@@ -0,0 +1,389 @@
+use ihex::Reader;
+use ihex_ext::*;
+
+const OVERLAPPING: &str = "\
+:0400000001020304F2
+:02000200AA034F
+:00000001FF
+";
+
+fn unpack_overlapping(overlap: OverlapPolicy) -> Result<MemoryImage, UnpackingError> {
+    Reader::new(OVERLAPPING)
+        .unpack(&UnpackOptions::new().overlap(overlap))
+        .map(|unpacked| unpacked.data)
+}
+
+#[test]
+fn overlap_policies() {
+    assert_eq!(
+        unpack_overlapping(OverlapPolicy::LastWins)
+            .unwrap()
+            .to_vec(),
+        (vec![0x01, 0x02, 0xAA, 0x03], 4)
+    );
+    assert_eq!(
+        unpack_overlapping(OverlapPolicy::FirstWins)
+            .unwrap()
+            .to_vec(),
+        (vec![0x01, 0x02, 0x03, 0x04], 4)
+    );
+    assert_eq!(
+        unpack_overlapping(OverlapPolicy::Error),
+        Err(UnpackingError::Overlap { address: 2, len: 2 })
+    );
+    assert_eq!(
+        unpack_overlapping(OverlapPolicy::ErrorIfDifferent),
+        Err(UnpackingError::Overlap { address: 2, len: 2 })
+    );
+}
+
+#[test]
+fn identical_overlap_allowed() {
+    let ihex = ":0400000001020304F2\n:020002000304F5\n:00000001FF\n";
+    let options = UnpackOptions::new().overlap(OverlapPolicy::ErrorIfDifferent);
+    let image = Reader::new(ihex).unpack(&options).unwrap().data;
+    assert_eq!(image.to_vec(), (vec![0x01, 0x02, 0x03, 0x04], 4));
+}
+
+#[test]
+fn entry_point() {
+    let ihex = ":0400000001020304F2\n:0400000508000101ED\n:00000001FF\n";
+    let unpacked = Reader::new(ihex).unpack(&UnpackOptions::default()).unwrap();
+    assert_eq!(unpacked.entry_point, Some(EntryPoint::Linear(0x0800_0101)));
+    assert_eq!(unpacked.used_bytes, 4);
+
+    let ihex = ":0400000508000101ED\n:0400000300001000E9\n:00000001FF\n";
+    assert_eq!(
+        Reader::new(ihex).unpack(&UnpackOptions::default()),
+        Err(UnpackingError::ConflictingEntryPoint(
+            EntryPoint::Linear(0x0800_0101),
+            EntryPoint::Segment { cs: 0, ip: 0x1000 },
+        ))
+    );
+}
+
+#[test]
+fn fill_pattern() {
+    let ihex = ":020002000304F5\n:00000001FF\n";
+    let options = UnpackOptions::new()
+        .fill_pattern(&[0xDE, 0xAD])
+        .size_limit(6);
+    let unpacked = Reader::new(ihex).unpack_vec(&options).unwrap();
+    assert_eq!(unpacked.data, vec![0xDE, 0xAD, 0x03, 0x04, 0xDE, 0xAD]);
+    assert_eq!(unpacked.used_bytes, 2);
+
+    let unpacked = Reader::new(ihex)
+        .unpack_array::<5>(&UnpackOptions::new().fill_byte(0x00))
+        .unwrap();
+    assert_eq!(unpacked.data, [0x00, 0x00, 0x03, 0x04, 0x00]);
+}
+
+#[test]
+fn missing_eof() {
+    let ihex = ":020002000304F5\n";
+    assert!(Reader::new(ihex).unpack(&UnpackOptions::new()).is_ok());
+    assert_eq!(
+        Reader::new(ihex).unpack(&UnpackOptions::new().strict_eof(true)),
+        Err(UnpackingError::MissingEof)
+    );
+}
+
+#[test]
+fn out_of_window() {
+    let ihex = "\
+:020000040800F2
+:04FFFC0001020304F7
+:020000040801F1
+:0400000005060708E2
+:00000001FF
+";
+    let options = UnpackOptions::new().base_offset(0x0800_FFFE).size_limit(4);
+    assert_eq!(
+        Reader::new(ihex).unpack(&options),
+        Err(UnpackingError::AddressBelowBase {
+            address: 0x0800_FFFC,
+            base_offset: 0x0800_FFFE,
+        })
+    );
+
+    let options = options.out_of_window(OutOfWindow::Collect);
+    let unpacked = Reader::new(ihex).unpack(&options).unwrap();
+    assert_eq!(unpacked.data.to_vec(), (vec![0x03, 0x04, 0x05, 0x06], 4));
+    let collected: Vec<_> = unpacked
+        .out_of_window
+        .segments()
+        .map(|s| (s.address(), s.data().to_vec()))
+        .collect();
+    assert_eq!(
+        collected,
+        vec![
+            (0x0800_FFFC, vec![0x01, 0x02]),
+            (0x0801_0002, vec![0x07, 0x08])
+        ]
+    );
+}
+
+#[test]
+fn detect_formats() {
+    assert_eq!(detect_format(b":00000001FF\r\n"), FileFormat::IntelHex);
+    assert_eq!(detect_format(b"\n\nS00600004844521B\n"), FileFormat::Srec);
+    assert_eq!(detect_format(b"@F000\n31 40 00 03\nq\n"), FileFormat::TiTxt);
+    assert_eq!(detect_format(b"\x7FELF\x01\x01\x01"), FileFormat::Elf);
+    assert_eq!(detect_format(b":\x00\x01\x02"), FileFormat::Binary);
+    assert_eq!(detect_format(b"Hello\n"), FileFormat::Unknown);
+    assert_eq!(
+        detect_format(b"\x00\x20\x00\x20\x41\x01\x00\x08"),
+        FileFormat::Binary
+    );
+}
+
+#[test]
+fn load_auto_corrupt_ihex() {
+    let path = std::env::temp_dir().join(format!("ihex_ext_bad_{}.hex", std::process::id()));
+    std::fs::write(&path, ":04000000010203G4F2\n:00000001FF\n").unwrap();
+    let err = load_file_auto(&path, &UnpackOptions::new()).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+
+    let LoadError::Unpacking(err) = err.kind() else {
+        panic!("unexpected error {:?}", err);
+    };
+    assert_eq!(err.line(), Some(1));
+    assert_eq!(
+        err.kind(),
+        &UnpackingError::Parsing(ihex::ReaderError::ContainsInvalidCharacters)
+    );
+}
+
+#[test]
+fn elf32_load_segments() {
+    use ihex_ext::elf::*;
+
+    let mut elf = vec![0; 84];
+    elf[..7].copy_from_slice(b"\x7FELF\x01\x01\x01");
+    elf[24..28].copy_from_slice(&0x0800_0009u32.to_le_bytes()); // e_entry
+    elf[28..32].copy_from_slice(&52u32.to_le_bytes()); // e_phoff
+    elf[42..44].copy_from_slice(&32u16.to_le_bytes()); // e_phentsize
+    elf[44..46].copy_from_slice(&1u16.to_le_bytes()); // e_phnum
+    elf[52..56].copy_from_slice(&1u32.to_le_bytes()); // p_type = PT_LOAD
+    elf[56..60].copy_from_slice(&84u32.to_le_bytes()); // p_offset
+    elf[60..64].copy_from_slice(&0x2000_0000u32.to_le_bytes()); // p_vaddr
+    elf[64..68].copy_from_slice(&0x0800_0000u32.to_le_bytes()); // p_paddr
+    elf[68..72].copy_from_slice(&4u32.to_le_bytes()); // p_filesz
+    elf[72..76].copy_from_slice(&8u32.to_le_bytes()); // p_memsz
+    elf.extend_from_slice(&[1, 2, 3, 4]);
+
+    let options = UnpackOptions::new();
+    let unpacked = unpack_elf(&elf, &options, LoadAddress::Physical).unwrap();
+    assert_eq!(
+        unpacked.data,
+        MemoryImage::from_slice(0x0800_0000, &[1, 2, 3, 4])
+    );
+    assert_eq!(unpacked.entry_point, Some(EntryPoint::Linear(0x0800_0009)));
+
+    let unpacked = unpack_elf(&elf, &options, LoadAddress::Virtual).unwrap();
+    assert_eq!(
+        unpacked.data,
+        MemoryImage::from_slice(0x2000_0000, &[1, 2, 3, 4])
+    );
+
+    assert_eq!(
+        unpack_elf(&elf[..80], &options, LoadAddress::Physical),
+        Err(UnpackingError::ElfParsing(ElfError::Truncated))
+    );
+
+    elf[24..28].copy_from_slice(&0u32.to_le_bytes());
+    let unpacked = unpack_elf(&elf, &options, LoadAddress::Physical).unwrap();
+    assert_eq!(unpacked.entry_point, None);
+    let options = options.zero_entry_point(true);
+    let unpacked = unpack_elf(&elf, &options, LoadAddress::Physical).unwrap();
+    assert_eq!(unpacked.entry_point, Some(EntryPoint::Linear(0)));
+}
+
+#[test]
+fn elf64_high_entry_point() {
+    use ihex_ext::elf::*;
+
+    let mut elf = vec![0; 120];
+    elf[..7].copy_from_slice(b"\x7FELF\x02\x01\x01");
+    elf[24..32].copy_from_slice(&0xFFFF_FFFF_8000_0000u64.to_le_bytes()); // e_entry
+    elf[32..40].copy_from_slice(&64u64.to_le_bytes()); // e_phoff
+    elf[54..56].copy_from_slice(&56u16.to_le_bytes()); // e_phentsize
+    elf[56..58].copy_from_slice(&1u16.to_le_bytes()); // e_phnum
+    elf[64..68].copy_from_slice(&1u32.to_le_bytes()); // p_type = PT_LOAD
+    elf[72..80].copy_from_slice(&120u64.to_le_bytes()); // p_offset
+    elf[88..96].copy_from_slice(&0x0010_0000u64.to_le_bytes()); // p_paddr
+    elf[96..104].copy_from_slice(&2u64.to_le_bytes()); // p_filesz
+    elf.extend_from_slice(&[1, 2]);
+
+    let unpacked = unpack_elf(&elf, &UnpackOptions::new(), LoadAddress::Physical).unwrap();
+    assert_eq!(unpacked.data, MemoryImage::from_slice(0x0010_0000, &[1, 2]));
+    assert_eq!(unpacked.entry_point, None);
+
+    elf[88..96].copy_from_slice(&(u64::MAX - 1).to_le_bytes()); // p_paddr
+    assert_eq!(
+        unpack_elf(&elf, &UnpackOptions::new(), LoadAddress::Physical),
+        Err(UnpackingError::AddressOverflow {
+            address: usize::MAX - 1,
+            len: 2
+        })
+    );
+}
+
+#[test]
+fn data_after_eof() {
+    let path = std::env::temp_dir().join(format!("ihex_ext_eof_{}.hex", std::process::id()));
+    std::fs::write(
+        &path,
+        ":020002000304F5\n:00000001FF\n\n  \n:020002000304F5\n",
+    )
+    .unwrap();
+    let lenient = load_file_image(&path, &UnpackOptions::new());
+    let strict = load_file_image(&path, &UnpackOptions::new().strict_eof(true));
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(lenient.is_ok());
+    let err = strict.unwrap_err();
+    assert_eq!(err.path(), Some(path.as_path()));
+    assert!(matches!(
+        err.kind(),
+        LoadError::Unpacking(UnpackingError::DataAfterEof { line: 5 })
+    ));
+}
+
+#[test]
+fn error_context() {
+    let path = std::env::temp_dir().join(format!("ihex_ext_ctx_{}.hex", std::process::id()));
+    std::fs::write(&path, ":020002000304F5\n\n:020002000305F5\n:00000001FF\n").unwrap();
+    let err = load_file_image(&path, &UnpackOptions::new()).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+
+    let LoadError::Unpacking(err) = err.kind() else {
+        panic!("unexpected error {:?}", err);
+    };
+    assert_eq!(err.line(), Some(3));
+    assert_eq!(
+        err.kind(),
+        &UnpackingError::Parsing(ihex::ReaderError::ChecksumMismatch(0xF4, 0xF5))
+    );
+    assert_eq!(
+        err.to_string(),
+        "Line 3 `:020002000305F5`: Error while parsing IHEX records: \
+         invalid checksum 'F4', expecting 'F5'"
+    );
+    // The message already includes the cause, so it isn't repeated as a source.
+    assert!(std::error::Error::source(err).is_none());
+}
+
+#[test]
+fn load_from_reader() {
+    let ihex: &[u8] = b":020002000304F5\r\n:0400000508000101ED\r\n:00000001FF\r\n";
+    assert_eq!(
+        load_reader_vec(ihex, 4, 0).unwrap(),
+        (vec![0xFF, 0xFF, 0x03, 0x04], 2)
+    );
+
+    let unpacked = load_reader_image(ihex, &UnpackOptions::new()).unwrap();
+    assert_eq!(unpacked.entry_point, Some(EntryPoint::Linear(0x0800_0101)));
+}
+
+#[test]
+fn reject_non_text() {
+    let ihex: &[u8] = b":020002000304F5\n:00000001FF\xC3\xA9\n";
+    assert!(matches!(
+        load_reader_image(ihex, &UnpackOptions::new()),
+        Err(LoadError::NotText { offset: 27 })
+    ));
+
+    let bin: &[u8] = b"\x00\x20\x00\x20\x41\x01\x00\x08";
+    assert!(matches!(
+        load_reader_image(bin, &UnpackOptions::new()),
+        Err(LoadError::LooksLikeBinary)
+    ));
+}
+
+#[test]
+fn word_addresses() {
+    let ihex = ":040002002800FF3F94\n:020000040001F9\n:020000000034CA\n:00000001FF\n";
+    let options = UnpackOptions::new()
+        .address_unit(AddressUnit::Word16)
+        .word_endian(Endian::Big);
+    let image = Reader::new(ihex).unpack(&options).unwrap().data;
+    let segments: Vec<_> = image
+        .segments()
+        .map(|s| (s.address(), s.data().to_vec()))
+        .collect();
+    assert_eq!(
+        segments,
+        vec![
+            (0x0_0004, vec![0x00, 0x28, 0x3F, 0xFF]),
+            (0x2_0000, vec![0x34, 0x00])
+        ]
+    );
+
+    let options = options.address_unit(AddressUnit::Word32);
+    assert_eq!(
+        Reader::new(":03000000010203F7\n:00000001FF\n").unpack(&options),
+        Err(UnpackingError::PartialWord(3))
+    );
+}
+
+#[test]
+fn segment_wraparound() {
+    fn segments(ihex: &str, options: &UnpackOptions) -> Vec<(usize, Vec<u8>)> {
+        let image = Reader::new(ihex).unpack(options).unwrap().data;
+        image
+            .segments()
+            .map(|s| (s.address(), s.data().to_vec()))
+            .collect()
+    }
+
+    let segment = ":020000021000EC\n:04FFFE0001020304F5\n:00000001FF\n";
+    assert_eq!(
+        segments(segment, &UnpackOptions::new()),
+        vec![(0x1_0000, vec![0x03, 0x04]), (0x1_FFFE, vec![0x01, 0x02])]
+    );
+    assert_eq!(
+        segments(segment, &UnpackOptions::new().segment_wrap(false)),
+        vec![(0x1_FFFE, vec![0x01, 0x02, 0x03, 0x04])]
+    );
+
+    // Linear addresses never wrap.
+    let linear = ":020000040001F9\n:04FFFE0001020304F5\n:00000001FF\n";
+    assert_eq!(
+        segments(linear, &UnpackOptions::new()),
+        vec![(0x1_FFFE, vec![0x01, 0x02, 0x03, 0x04])]
+    );
+}
+
+#[test]
+fn out_of_window_whole_records() {
+    // One record wholly below the window, one inside, and one wholly above it.
+    let ihex = ":0400000001020304F2\n:02008000AABB19\n:0401000011121314B1\n:00000001FF\n";
+    let options = UnpackOptions::new()
+        .base_offset(0x80)
+        .size_limit(0x10)
+        .out_of_window(OutOfWindow::Collect);
+    let unpacked = Reader::new(ihex).unpack(&options).unwrap();
+    assert_eq!(unpacked.data, MemoryImage::from_slice(0, &[0xAA, 0xBB]));
+    let mut expected = MemoryImage::from_slice(0x0, &[0x01, 0x02, 0x03, 0x04]);
+    expected.write(0x100, &[0x11, 0x12, 0x13, 0x14]);
+    assert_eq!(unpacked.out_of_window, expected);
+
+    let options = options.out_of_window(OutOfWindow::Skip);
+    let unpacked = Reader::new(ihex).unpack(&options).unwrap();
+    assert_eq!(unpacked.data, MemoryImage::from_slice(0, &[0xAA, 0xBB]));
+    assert!(unpacked.out_of_window.is_empty());
+}
+
+#[test]
+fn to_vec_minimal_reader_error() {
+    let ihex = ":020002000304F5\n:0400000508000101ED\n:0400000300001000E9\n:00000001FF\n";
+    assert_eq!(
+        Reader::new(ihex).to_vec_minimal(0),
+        Ok((vec![0xFF, 0xFF, 0x03, 0x04], 2))
+    );
+    assert_eq!(
+        Reader::new(":020002000305F5\n").to_vec_minimal(0),
+        Err(ihex::ReaderError::ChecksumMismatch(0xF4, 0xF5))
+    );
+}